@@ -2,6 +2,9 @@ pub mod utils;
 
 use crate::utils::*;
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 use std::convert::TryInto;
 
@@ -13,7 +16,20 @@ pub mod constants {
     pub const CONFIG_PDA_SEED: &[u8] = b"MINE_TOGETHER_CONFIG";
     pub const MINE_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINE";
     pub const MINER_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINER";
+    pub const MINE_MEMBER_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINE_MEMBER";
+    pub const MINE_FEES_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINE_FEES";
+    pub const WALLET_PURCHASE_PDA_SEED: &[u8] = b"MINE_TOGETHER_WALLET_PURCHASE";
+    pub const PENDING_CONFIG_CHANGE_PDA_SEED: &[u8] = b"MINE_TOGETHER_PENDING_CONFIG_CHANGE";
     pub const SHARES_LIMIT: usize = 400;
+    pub const NAME_MAX_LEN: usize = 50;
+    pub const URI_MAX_LEN: usize = 200;
+    pub const FEE_TIERS_LIMIT: usize = 8;
+    pub const MINER_NFT_SYMBOL: &str = "AURYMINER";
+    pub const MINER_NFT_NAME_MAX_LEN: usize = 32;
+    pub const KEEPER_TIP_LIMIT: u64 = 500; // 5%
+    pub const FEE_SPLITS_LIMIT: usize = 8;
+    pub const VIRTUAL_X_SHARES: u64 = 1_000_000_000; // 1 aury
+    pub const CONFIG_CHANGE_DELAY: u64 = 2 * 24 * 60 * 60; // 2 days
 }
 
 #[cfg(feature = "local-testing")]
@@ -22,7 +38,20 @@ pub mod constants {
     pub const CONFIG_PDA_SEED: &[u8] = b"MINE_TOGETHER_CONFIG";
     pub const MINE_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINE";
     pub const MINER_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINER";
+    pub const MINE_MEMBER_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINE_MEMBER";
+    pub const MINE_FEES_PDA_SEED: &[u8] = b"MINE_TOGETHER_MINE_FEES";
+    pub const WALLET_PURCHASE_PDA_SEED: &[u8] = b"MINE_TOGETHER_WALLET_PURCHASE";
+    pub const PENDING_CONFIG_CHANGE_PDA_SEED: &[u8] = b"MINE_TOGETHER_PENDING_CONFIG_CHANGE";
     pub const SHARES_LIMIT: usize = 400;
+    pub const NAME_MAX_LEN: usize = 50;
+    pub const URI_MAX_LEN: usize = 200;
+    pub const FEE_TIERS_LIMIT: usize = 8;
+    pub const MINER_NFT_SYMBOL: &str = "AURYMINER";
+    pub const MINER_NFT_NAME_MAX_LEN: usize = 32;
+    pub const KEEPER_TIP_LIMIT: u64 = 500; // 5%
+    pub const FEE_SPLITS_LIMIT: usize = 8;
+    pub const VIRTUAL_X_SHARES: u64 = 1_000_000_000; // 1 aury
+    pub const CONFIG_CHANGE_DELAY: u64 = 3; // 3 seconds
 }
 
 #[program]
@@ -45,6 +74,7 @@ pub mod mine_together {
 
         let config_account = &mut ctx.accounts.config_account;
 
+        config_account.version = ConfigAccount::VERSION;
         config_account.admin_key = *ctx.accounts.initializer.key;
         config_account.min_mine_fee = min_mine_fee;
         config_account.max_mine_fee = max_mine_fee;
@@ -54,47 +84,148 @@ pub mod mine_together {
     }
 
     #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
-    pub fn update_admin(
-        ctx: Context<UpdateConfig>,
-        _nonce_config: u8,
-        new_admin: Pubkey,
-    ) -> ProgramResult {
+    pub fn toggle_freeze_program(ctx: Context<UpdateConfig>, _nonce_config: u8) -> ProgramResult {
         let config_account = &mut ctx.accounts.config_account;
 
-        config_account.admin_key = new_admin;
+        config_account.freeze_program = !config_account.freeze_program;
 
         Ok(())
     }
 
     #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
-    pub fn toggle_freeze_program(ctx: Context<UpdateConfig>, _nonce_config: u8) -> ProgramResult {
-        let config_account = &mut ctx.accounts.config_account;
+    pub fn queue_config_change(
+        ctx: Context<QueueConfigChange>,
+        _nonce_config: u8,
+        _nonce_pending_config_change: u8,
+        change: ConfigChange,
+    ) -> ProgramResult {
+        match change {
+            ConfigChange::Mine {
+                min_mine_fee,
+                max_mine_fee,
+                ..
+            } => {
+                if !(min_mine_fee > 0
+                    && min_mine_fee < max_mine_fee
+                    && max_mine_fee < FEE_MULTIPLIER)
+                {
+                    return Err(ErrorCode::InvalidMineFee.into());
+                }
+            }
+            ConfigChange::ProtocolFee {
+                protocol_fee,
+                treasury,
+            } => {
+                if protocol_fee > FEE_MULTIPLIER {
+                    return Err(ErrorCode::InvalidProtocolFee.into());
+                }
 
-        config_account.freeze_program = !config_account.freeze_program;
+                // remaining accounts: the treasury, which must be an aury
+                // token account
+                let treasury_info = ctx
+                    .remaining_accounts
+                    .first()
+                    .ok_or(ErrorCode::InvalidTreasuryAccount)?;
+                let treasury_account = Account::<TokenAccount>::try_from(treasury_info)
+                    .map_err(|_| ErrorCode::InvalidTreasuryAccount)?;
+                if !(*treasury_info.key == treasury
+                    && treasury_account.mint
+                        == constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap())
+                {
+                    return Err(ErrorCode::InvalidTreasuryAccount.into());
+                }
+            }
+            ConfigChange::KeeperTip { keeper_tip } => {
+                if keeper_tip > constants::KEEPER_TIP_LIMIT {
+                    return Err(ErrorCode::InvalidKeeperTip.into());
+                }
+            }
+            ConfigChange::CancelFee { cancel_fee } => {
+                if cancel_fee > FEE_MULTIPLIER {
+                    return Err(ErrorCode::InvalidCancelFee.into());
+                }
+                // cancels pay their fee to the treasury, so one must be set
+                if cancel_fee > 0 && ctx.accounts.config_account.treasury == Pubkey::default() {
+                    return Err(ErrorCode::InvalidTreasuryAccount.into());
+                }
+            }
+            ConfigChange::Admin { .. } => {}
+        }
+
+        let pending_config_change = &mut ctx.accounts.pending_config_change;
+
+        // a fixed notice period, so it can't be shortened by a change of its
+        // own
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+        pending_config_change.version = PendingConfigChange::VERSION;
+        pending_config_change.change = change;
+        pending_config_change.queued_at = now;
+        pending_config_change.execute_at = now + constants::CONFIG_CHANGE_DELAY;
+
+        emit!(ConfigChangeQueued {
+            pending_config_change: pending_config_change.key(),
+            change,
+            execute_at: pending_config_change.execute_at,
+        });
 
         Ok(())
     }
 
     #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
-    pub fn update_config_mine(
-        ctx: Context<UpdateConfig>,
+    pub fn execute_config_change(
+        ctx: Context<ExecuteConfigChange>,
         _nonce_config: u8,
-        new_min_mine_fee: u64,
-        new_max_mine_fee: u64,
-        new_mine_update_delay: u64,
+        _nonce_pending_config_change: u8,
     ) -> ProgramResult {
-        if !(new_min_mine_fee > 0
-            && new_min_mine_fee < new_max_mine_fee
-            && new_max_mine_fee < FEE_MULTIPLIER)
-        {
-            return Err(ErrorCode::InvalidMineFee.into());
+        let config_account = &mut ctx.accounts.config_account;
+        let pending_config_change = &ctx.accounts.pending_config_change;
+
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+        pending_config_change.assert_executable(now)?;
+
+        match pending_config_change.change {
+            ConfigChange::Mine {
+                min_mine_fee,
+                max_mine_fee,
+                mine_update_delay,
+            } => {
+                config_account.min_mine_fee = min_mine_fee;
+                config_account.max_mine_fee = max_mine_fee;
+                config_account.mine_update_delay = mine_update_delay;
+            }
+            ConfigChange::Admin { new_admin } => {
+                config_account.admin_key = new_admin;
+            }
+            ConfigChange::ProtocolFee {
+                protocol_fee,
+                treasury,
+            } => {
+                config_account.protocol_fee = protocol_fee;
+                config_account.treasury = treasury;
+            }
+            ConfigChange::KeeperTip { keeper_tip } => {
+                config_account.keeper_tip = keeper_tip;
+            }
+            ConfigChange::CancelFee { cancel_fee } => {
+                config_account.cancel_fee = cancel_fee;
+            }
         }
 
-        let config_account = &mut ctx.accounts.config_account;
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
+    pub fn cancel_config_change(
+        ctx: Context<CancelConfigChange>,
+        _nonce_config: u8,
+        _nonce_pending_config_change: u8,
+    ) -> ProgramResult {
+        let pending_config_change = &ctx.accounts.pending_config_change;
 
-        config_account.min_mine_fee = new_min_mine_fee;
-        config_account.max_mine_fee = new_max_mine_fee;
-        config_account.mine_update_delay = new_mine_update_delay;
+        emit!(ConfigChangeCancelled {
+            pending_config_change: pending_config_change.key(),
+            change: pending_config_change.change,
+        });
 
         Ok(())
     }
@@ -109,14 +240,22 @@ pub mod mine_together {
         cost: u64,
         duration: u64,
         limit: u64,
+        uri: Option<String>,
     ) -> ProgramResult {
         let miner_account = &mut ctx.accounts.miner_account;
+        let uri = uri.unwrap_or_default();
+
+        assert_valid_name(&name)?;
+        assert_valid_uri(&uri)?;
 
         // update the miner
+        miner_account.version = MinerAccount::VERSION;
         miner_account.name = name;
+        miner_account.uri = uri;
         miner_account.cost = cost;
         miner_account.duration = duration;
         miner_account.limit = limit;
+        miner_account.power_multiplier = BPS_MULTIPLIER;
 
         Ok(())
     }
@@ -131,46 +270,292 @@ pub mod mine_together {
     }
 
     #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
-    pub fn remove_miner(ctx: Context<RemoveMiner>, _nonce_config: u8) -> ProgramResult {
+    pub fn update_miner_collection(
+        ctx: Context<UpdateMiner>,
+        _nonce_config: u8,
+        required_collection: Pubkey,
+    ) -> ProgramResult {
+        let miner_account = &mut ctx.accounts.miner_account;
+
+        miner_account.required_collection = required_collection;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
+    pub fn update_miner_sale(
+        ctx: Context<UpdateMiner>,
+        _nonce_config: u8,
+        max_per_wallet: u64,
+        sale_start: u64,
+        sale_end: u64,
+    ) -> ProgramResult {
+        if !(sale_end == 0 || sale_start < sale_end) {
+            return Err(ErrorCode::InvalidSaleWindow.into());
+        }
+
+        let miner_account = &mut ctx.accounts.miner_account;
+
+        miner_account.max_per_wallet = max_per_wallet;
+        miner_account.sale_start = sale_start;
+        miner_account.sale_end = sale_end;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
+    pub fn update_miner_pricing(
+        ctx: Context<UpdateMiner>,
+        _nonce_config: u8,
+        pricing: PricingCurve,
+        price_step: u64,
+        price_floor: u64,
+    ) -> ProgramResult {
+        let miner_account = &mut ctx.accounts.miner_account;
+
+        if pricing == PricingCurve::DutchAuction && miner_account.sale_start == 0 {
+            return Err(ErrorCode::InvalidSaleWindow.into());
+        }
+
+        miner_account.pricing = pricing;
+        miner_account.price_step = price_step;
+        miner_account.price_floor = price_floor;
+
         Ok(())
     }
 
-    pub fn purchase_miner(
-        ctx: Context<PurchaseMiner>,
+    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
+    pub fn update_miner_power_multiplier(
+        ctx: Context<UpdateMiner>,
         _nonce_config: u8,
+        power_multiplier: u64,
+    ) -> ProgramResult {
+        if power_multiplier == 0 {
+            return Err(ErrorCode::InvalidPowerMultiplier.into());
+        }
+
+        let miner_account = &mut ctx.accounts.miner_account;
+
+        miner_account.power_multiplier = power_multiplier;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
+    pub fn remove_miner(ctx: Context<RemoveMiner>, _nonce_config: u8) -> ProgramResult {
+        Ok(())
+    }
+
+    pub fn purchase_miner<'info>(
+        ctx: Context<'_, '_, '_, 'info, PurchaseMiner<'info>>,
+        nonce_config: u8,
+        _nonce_user_miner: u8,
+        _nonce_aury_vault: u8,
+        _nonce_wallet_purchase: u8,
+        amount: u64,
+        max_total_cost: u64,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let miner_account = &mut ctx.accounts.miner_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+
+        let config_account_seeds = &[constants::CONFIG_PDA_SEED.as_ref(), &[nonce_config]];
+        let config_account_signer = &config_account_seeds[..];
+        purchase_position(PurchaseParams {
+            miner_key: miner_account.key(),
+            miner_account,
+            user_miner_account: &mut ctx.accounts.user_miner_account,
+            wallet_purchase_account: &mut ctx.accounts.wallet_purchase_account,
+            amount,
+            max_total_cost,
+            buyer: ctx.accounts.aury_from.owner,
+            proofs: ctx.remaining_accounts,
+            aury_from: ctx.accounts.aury_from.to_account_info(),
+            aury_from_authority: ctx.accounts.aury_from_authority.to_account_info(),
+            aury_mint: aury_mint.to_account_info(),
+            aury_decimals: aury_mint.decimals,
+            aury_vault: ctx.accounts.aury_vault.to_account_info(),
+            position_mint: ctx.accounts.position_mint.to_account_info(),
+            position_token_account: ctx.accounts.position_token_account.to_account_info(),
+            metadata: ctx.accounts.metadata.to_account_info(),
+            master_edition: ctx.accounts.master_edition.to_account_info(),
+            authority: config_account.to_account_info(),
+            authority_signer_seeds: config_account_signer,
+            token_metadata_program: ctx.accounts.token_metadata_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+        })
+    }
+
+    pub fn purchase_and_mine<'info>(
+        ctx: Context<'_, '_, '_, 'info, PurchaseAndMine<'info>>,
+        nonce_config: u8,
         _nonce_user_miner: u8,
         _nonce_aury_vault: u8,
+        _nonce_wallet_purchase: u8,
         amount: u64,
+        max_total_cost: u64,
+        min_x_shares_out: u64,
     ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
         let miner_account = &mut ctx.accounts.miner_account;
+        let mine_account = &mut ctx.accounts.mine_account;
         let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+        let buyer = ctx.accounts.aury_from.owner;
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+
+        // remaining accounts: the miner's collection proofs, then the mine's
+        // access proofs
+        let proofs_used = if miner_account.required_collection != Pubkey::default() {
+            2
+        } else {
+            0
+        };
+
+        let mine_key = mine_account.key();
+        mine_account.assert_member(mine_key, &buyer, &ctx.remaining_accounts[proofs_used..])?;
+
+        let config_account_seeds = &[constants::CONFIG_PDA_SEED.as_ref(), &[nonce_config]];
+        let config_account_signer = &config_account_seeds[..];
+        purchase_position(PurchaseParams {
+            miner_key: miner_account.key(),
+            miner_account,
+            user_miner_account,
+            wallet_purchase_account: &mut ctx.accounts.wallet_purchase_account,
+            amount,
+            max_total_cost,
+            buyer,
+            proofs: ctx.remaining_accounts,
+            aury_from: ctx.accounts.aury_from.to_account_info(),
+            aury_from_authority: ctx.accounts.aury_from_authority.to_account_info(),
+            aury_mint: aury_mint.to_account_info(),
+            aury_decimals: aury_mint.decimals,
+            aury_vault: ctx.accounts.aury_vault.to_account_info(),
+            position_mint: ctx.accounts.position_mint.to_account_info(),
+            position_token_account: ctx.accounts.position_token_account.to_account_info(),
+            metadata: ctx.accounts.metadata.to_account_info(),
+            master_edition: ctx.accounts.master_edition.to_account_info(),
+            authority: config_account.to_account_info(),
+            authority_signer_seeds: config_account_signer,
+            token_metadata_program: ctx.accounts.token_metadata_program.to_account_info(),
+            token_program: ctx.accounts.token_program.to_account_info(),
+            system_program: ctx.accounts.system_program.to_account_info(),
+            rent: ctx.accounts.rent.to_account_info(),
+        })?;
+
+        // put the miner to work right away
+        let x_total_amount = mine_account.x_total_amount;
+        mine_account.join(
+            mine_key,
+            &ctx.accounts.mine_fees_account,
+            user_miner_account,
+            now,
+        )?;
+
+        let x_shares = mine_account.x_total_amount - x_total_amount;
+        if x_shares < min_x_shares_out {
+            return Err(ErrorCode::XSharesBelowMin.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn cancel_miner<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelMiner<'info>>,
+        _nonce_config: u8,
+        _nonce_user_miner: u8,
+        nonce_aury_vault: u8,
+        _nonce_wallet_purchase: u8,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let miner_account = &mut ctx.accounts.miner_account;
+        let user_miner_account = &ctx.accounts.user_miner_account;
+        let aury_mint = &ctx.accounts.aury_mint;
         let aury_vault = &mut ctx.accounts.aury_vault;
-        let aury_from = &mut ctx.accounts.aury_from;
-        let aury_from_authority = &ctx.accounts.aury_from_authority;
+        let aury_to = &mut ctx.accounts.aury_to;
+        let treasury = &mut ctx.accounts.treasury;
+        let aury_to_authority = &ctx.accounts.aury_to_authority;
         let token_program = &ctx.accounts.token_program;
 
-        miner_account.assert_purchasable(amount)?;
+        user_miner_account.assert_holder(aury_to_authority.key, ctx.remaining_accounts)?;
+        if user_miner_account.mining_start_at > 0 {
+            return Err(ErrorCode::MinerAlreadyMining.into());
+        }
 
-        // transfer aury to the vault
-        let power = miner_account.cost * amount;
+        // compute aury vault account signer seeds
+        let aury_mint_key = aury_mint.key();
+        let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
+        let aury_vault_account_signer = &aury_vault_account_seeds[..];
 
+        // refund the power to the user, less the cancel fee
+        let cancel_fee_amount: u64 = (user_miner_account.power as u128)
+            .checked_mul(config_account.cancel_fee as u128)
+            .unwrap()
+            .checked_div(FEE_MULTIPLIER as u128)
+            .unwrap()
+            .try_into()
+            .unwrap();
         spl_token_transfer(TokenTransferParams {
-            source: aury_from.to_account_info(),
-            destination: aury_vault.to_account_info(),
-            amount: power,
-            authority: aury_from_authority.to_account_info(),
-            authority_signer_seeds: &[],
+            source: aury_vault.to_account_info(),
+            mint: aury_mint.to_account_info(),
+            decimals: aury_mint.decimals,
+            destination: aury_to.to_account_info(),
+            amount: user_miner_account.power - cancel_fee_amount,
+            authority: aury_vault.to_account_info(),
+            authority_signer_seeds: aury_vault_account_signer,
             token_program: token_program.to_account_info(),
         })?;
 
-        // update the user miner
-        user_miner_account.owner = *aury_from_authority.key;
-        user_miner_account.miner_type = miner_account.key();
-        user_miner_account.power = power;
-        user_miner_account.duration = miner_account.duration;
+        // transfer the cancel fee to the treasury
+        if cancel_fee_amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: treasury.to_account_info(),
+                amount: cancel_fee_amount,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // release the miners back to the miner_account supply and to the
+        // buyer's wallet cap, positions bought before caps have no record
+        let purchased_amount = user_miner_account.purchased_amount(miner_account);
+        miner_account.total_purchased = miner_account
+            .total_purchased
+            .saturating_sub(purchased_amount);
+
+        let wallet_purchase_info = &ctx.accounts.wallet_purchase_account;
+        if wallet_purchase_info.owner == ctx.program_id {
+            let mut wallet_purchase =
+                Account::<WalletPurchaseAccount>::try_from(wallet_purchase_info)?;
+            wallet_purchase.release(purchased_amount);
+            write_account(wallet_purchase_info, &*wallet_purchase)?;
+        }
+
+        // burn the position nft along with the miner
+        if user_miner_account.position_mint != Pubkey::default() {
+            let position_mint = ctx
+                .remaining_accounts
+                .get(1)
+                .ok_or(ErrorCode::InvalidAccounts)?;
+            if *position_mint.key != user_miner_account.position_mint {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
 
-        // update the miner_account
-        miner_account.total_purchased += amount;
+            spl_token_burn(TokenBurnParams {
+                mint: position_mint.clone(),
+                source: ctx.remaining_accounts[0].clone(),
+                amount: 1,
+                authority: aury_to_authority.to_account_info(),
+                authority_signer_seeds: &[],
+                token_program: token_program.to_account_info(),
+            })?;
+        }
 
         Ok(())
     }
@@ -179,21 +564,31 @@ pub mod mine_together {
         ctx: Context<CreateMine>,
         _nonce_config: u8,
         _nonce_mine: u8,
+        _nonce_mine_fees: u8,
         name: String,
         fee: u64,
+        uri: Option<String>,
     ) -> ProgramResult {
         let config_account = &ctx.accounts.config_account;
         let mine_account = &mut ctx.accounts.mine_account;
-        let fee_to = &ctx.accounts.fee_to;
+        let mine_fees_account = &mut ctx.accounts.mine_fees_account;
         let owner = &ctx.accounts.owner;
+        let uri = uri.unwrap_or_default();
 
+        assert_valid_name(&name)?;
+        assert_valid_uri(&uri)?;
         config_account.assert_mine_fee(fee)?;
 
         // update the mine_account
+        mine_account.version = MineAccount::VERSION;
         mine_account.owner = *owner.key;
         mine_account.name = name;
+        mine_account.uri = uri;
         mine_account.fee = fee;
-        mine_account.fee_to = fee_to.key();
+
+        // the fee tiers and splits live next to the mine
+        mine_fees_account.version = MineFeesAccount::VERSION;
+        mine_fees_account.mine = mine_account.key();
 
         Ok(())
     }
@@ -211,6 +606,67 @@ pub mod mine_together {
         Ok(())
     }
 
+    #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
+    pub fn update_mine_capacity(
+        ctx: Context<UpdateMineCapacity>,
+        _nonce_mine: u8,
+        max_total_amount: u64,
+        max_miners: u64,
+        min_power: u64,
+    ) -> ProgramResult {
+        let mine_account = &mut ctx.accounts.mine_account;
+
+        // active_miners starts at zero on mines migrated from the legacy
+        // layout, so there max_miners only counts miners that joined after the
+        // migration and can be exceeded until the earlier ones have left
+        mine_account.max_total_amount = max_total_amount;
+        mine_account.max_miners = max_miners;
+        mine_account.min_power = min_power;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
+    pub fn update_mine_access(
+        ctx: Context<UpdateMineAccess>,
+        _nonce_mine: u8,
+        access: MineAccess,
+        access_collection: Pubkey,
+    ) -> ProgramResult {
+        if access == MineAccess::Collection && access_collection == Pubkey::default() {
+            return Err(ErrorCode::InvalidMineAccess.into());
+        }
+
+        let mine_account = &mut ctx.accounts.mine_account;
+
+        mine_account.access = access;
+        mine_account.access_collection = access_collection;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
+    pub fn add_mine_member(
+        ctx: Context<AddMineMember>,
+        _nonce_mine: u8,
+        _nonce_mine_member: u8,
+        member: Pubkey,
+    ) -> ProgramResult {
+        let mine_account = &ctx.accounts.mine_account;
+        let mine_member_account = &mut ctx.accounts.mine_member_account;
+
+        mine_member_account.version = MineMemberAccount::VERSION;
+        mine_member_account.mine = mine_account.key();
+        mine_member_account.member = member;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
+    pub fn remove_mine_member(ctx: Context<RemoveMineMember>, _nonce_mine: u8) -> ProgramResult {
+        Ok(())
+    }
+
     #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
     pub fn update_mine(
         ctx: Context<UpdateMine>,
@@ -218,312 +674,2147 @@ pub mod mine_together {
         _nonce_mine: u8,
         name: String,
         fee: u64,
+        uri: Option<String>,
     ) -> ProgramResult {
         let config_account = &ctx.accounts.config_account;
         let mine_account = &mut ctx.accounts.mine_account;
-        let fee_to = &ctx.accounts.fee_to;
 
+        // the uri is left as is when not given
+        let uri = uri.unwrap_or_else(|| mine_account.uri.clone());
+
+        assert_valid_name(&name)?;
+        assert_valid_uri(&uri)?;
         config_account.assert_mine_fee(fee)?;
         mine_account.assert_updatable(config_account.mine_update_delay)?;
 
         // update the mine_account
+        let now = Clock::get().unwrap().unix_timestamp as u64;
         mine_account.name = name;
+        mine_account.uri = uri;
         mine_account.fee = fee;
-        mine_account.fee_to = fee_to.key();
-        mine_account.last_updated_at = Clock::get().unwrap().unix_timestamp as u64;
+        mine_account.last_updated_at = now;
+
+        // miners already in the mine keep the fee they joined with, new ones
+        // have until the next possible update to react
+        emit!(MineFeeUpdated {
+            mine: mine_account.key(),
+            fee: mine_account.fee,
+            next_update_at: now + config_account.mine_update_delay,
+        });
 
         Ok(())
     }
 
-    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
-    pub fn reward_to_mine(
-        ctx: Context<RewardToMine>,
+    #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
+    pub fn update_mine_fee_tiers(
+        ctx: Context<UpdateMineFeeTiers>,
         _nonce_config: u8,
-        _nonce_aury_vault: u8,
-        amount: u64,
+        _nonce_mine: u8,
+        fee_tiers: Vec<FeeTier>,
     ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
         let mine_account = &mut ctx.accounts.mine_account;
-        let aury_vault = &mut ctx.accounts.aury_vault;
-        let aury_from = &mut ctx.accounts.aury_from;
-        let admin = &ctx.accounts.admin;
-        let token_program = &ctx.accounts.token_program;
+        let mine_fees_account = &mut ctx.accounts.mine_fees_account;
 
-        // transfer aury to the vault
-        spl_token_transfer(TokenTransferParams {
-            source: aury_from.to_account_info(),
-            destination: aury_vault.to_account_info(),
-            amount: amount,
-            authority: admin.to_account_info(),
-            authority_signer_seeds: &[],
-            token_program: token_program.to_account_info(),
-        })?;
+        if fee_tiers.len() > constants::FEE_TIERS_LIMIT {
+            return Err(ErrorCode::TooManyFeeTiers.into());
+        }
+        for fee_tier in fee_tiers.iter() {
+            config_account.assert_mine_fee(fee_tier.fee)?;
+        }
+        mine_account.assert_updatable(config_account.mine_update_delay)?;
 
-        // update mine_account info
-        mine_account.total_amount += amount;
+        // update the mine_account
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+        mine_fees_account.fee_tiers = fee_tiers;
+        mine_account.last_updated_at = now;
 
-        // update mine_account shares
-        if mine_account.x_total_amount > 0 {
-            if mine_account.shares.len() == constants::SHARES_LIMIT {
-                mine_account.shares.remove(0);
-            }
-            let aury_share = AuryShare {
-                timestamp: Clock::get().unwrap().unix_timestamp as u64,
-                token_amount: mine_account.total_amount,
-                x_token_amount: mine_account.x_total_amount,
-            };
-            mine_account.shares.push(aury_share);
+        emit!(MineFeeUpdated {
+            mine: mine_account.key(),
+            fee: mine_account.fee,
+            next_update_at: now + config_account.mine_update_delay,
+        });
+
+        Ok(())
+    }
+
+    pub fn sync_mine_fee(ctx: Context<SyncMineFee>, _nonce_config: u8) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let mine_account = &mut ctx.accounts.mine_account;
+        let mine_fees_account = &mut ctx.accounts.mine_fees_account;
+
+        // clamp the fee and the fee tiers to the current config bounds
+        let mut synced = false;
+        let fee = config_account.clamp_mine_fee(mine_account.fee);
+        if fee != mine_account.fee {
+            mine_account.fee = fee;
+            synced = true;
+        }
+        for fee_tier in mine_fees_account.fee_tiers.iter_mut() {
+            let fee = config_account.clamp_mine_fee(fee_tier.fee);
+            if fee != fee_tier.fee {
+                fee_tier.fee = fee;
+                synced = true;
+            }
+        }
+
+        // the owner's update delay is left untouched
+        if synced {
+            emit!(MineFeeUpdated {
+                mine: mine_account.key(),
+                fee: mine_account.fee,
+                next_update_at: mine_account.last_updated_at + config_account.mine_update_delay,
+            });
         }
 
         Ok(())
     }
 
-    pub fn add_miners_to_mine(
-        ctx: Context<AddMinersToMine>,
-        _nonce_user_miner: u8,
+    #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
+    pub fn update_mine_fee_splits(
+        ctx: Context<UpdateMineFeeSplits>,
+        _nonce_config: u8,
+        _nonce_mine: u8,
+        fee_splits: Vec<FeeSplit>,
     ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
         let mine_account = &mut ctx.accounts.mine_account;
-        let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let mine_fees_account = &mut ctx.accounts.mine_fees_account;
+
+        if fee_splits.len() > constants::FEE_SPLITS_LIMIT {
+            return Err(ErrorCode::TooManyFeeSplits.into());
+        }
+        let total_share = fee_splits
+            .iter()
+            .try_fold(0u64, |total, split| match split.share {
+                0 => None,
+                share => total.checked_add(share),
+            });
+        if !fee_splits.is_empty() && total_share != Some(FEE_MULTIPLIER) {
+            return Err(ErrorCode::InvalidFeeSplits.into());
+        }
+
+        // remaining accounts: the recipients, in the order of the fee splits,
+        // which must be aury token accounts for the fees to be distributable
+        if ctx.remaining_accounts.len() != fee_splits.len() {
+            return Err(ErrorCode::InvalidFeeSplits.into());
+        }
+        let aury_mint_key = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap();
+        for (split, recipient) in fee_splits.iter().zip(ctx.remaining_accounts.iter()) {
+            if *recipient.key != split.recipient {
+                return Err(ErrorCode::InvalidFeeSplits.into());
+            }
+
+            let recipient = Account::<TokenAccount>::try_from(recipient)
+                .map_err(|_| ErrorCode::InvalidFeeSplits)?;
+            if recipient.mint != aury_mint_key {
+                return Err(ErrorCode::InvalidFeeSplits.into());
+            }
+        }
+        mine_account.assert_updatable(config_account.mine_update_delay)?;
+
+        // update the mine_account
         let now = Clock::get().unwrap().unix_timestamp as u64;
+        mine_fees_account.fee_splits = fee_splits;
+        mine_account.last_updated_at = now;
 
-        if mine_account.total_amount == 0 || mine_account.x_total_amount == 0 {
-            mine_account.x_total_amount += user_miner_account.power;
-            user_miner_account.x_aury_amount += user_miner_account.power;
-        } else {
-            let what: u64 = (user_miner_account.power as u128)
-                .checked_mul(mine_account.x_total_amount as u128)
-                .unwrap()
-                .checked_div(mine_account.total_amount as u128)
-                .unwrap()
-                .try_into()
-                .unwrap();
-
-            mine_account.x_total_amount += what;
-            user_miner_account.x_aury_amount += what;
-        }
-        mine_account.total_amount += user_miner_account.power;
-        user_miner_account.mine_key = mine_account.key();
-        user_miner_account.mining_start_at = now;
+        emit!(MineFeeSplitsUpdated {
+            mine: mine_account.key(),
+            fee_splits: mine_fees_account.fee_splits.clone(),
+            next_update_at: now + config_account.mine_update_delay,
+        });
 
         Ok(())
     }
 
-    pub fn claim_miner(
-        ctx: Context<ClaimMiner>,
-        _nonce_user_miner: u8,
+    pub fn distribute_mine_fees<'info>(
+        ctx: Context<'_, '_, '_, 'info, DistributeMineFees<'info>>,
         nonce_aury_vault: u8,
     ) -> ProgramResult {
         let mine_account = &mut ctx.accounts.mine_account;
-        let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let fee_splits = &ctx.accounts.mine_fees_account.fee_splits;
+        let aury_mint = &ctx.accounts.aury_mint;
         let aury_vault = &mut ctx.accounts.aury_vault;
-        let aury_to = &mut ctx.accounts.aury_to;
-        let fee_to = &mut ctx.accounts.fee_to;
         let token_program = &ctx.accounts.token_program;
 
-        user_miner_account.assert_claimable(mine_account.key())?;
-
-        // determine user reward amount
-        let x_aury = user_miner_account.x_aury_amount;
-        let mut what = 0;
-        let mining_end_timestamp = user_miner_account.mining_start_at + user_miner_account.duration;
-
-        for share in mine_account.shares.iter().rev() {
-            if share.timestamp <= mining_end_timestamp {
-                what = (x_aury as u128)
-                    .checked_mul(share.token_amount as u128)
-                    .unwrap()
-                    .checked_div(share.x_token_amount as u128)
-                    .unwrap()
-                    .try_into()
-                    .unwrap();
-                if what > user_miner_account.power {
-                    what -= user_miner_account.power;
-                } else {
-                    what = 0;
-                }
-                break;
-            }
+        // remaining accounts: the recipients, in the order of the fee splits
+        if fee_splits.is_empty() || ctx.remaining_accounts.len() != fee_splits.len() {
+            return Err(ErrorCode::InvalidFeeSplits.into());
         }
 
         // compute aury vault account signer seeds
-        let aury_mint_key = ctx.accounts.aury_mint.key();
+        let aury_mint_key = aury_mint.key();
         let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
         let aury_vault_account_signer = &aury_vault_account_seeds[..];
 
-        // transfer aury to the user
-        let reward_amount: u64 = (what as u128)
-            .checked_mul((FEE_MULTIPLIER - mine_account.fee) as u128)
+        // transfer each recipient its share, the last one takes the rounding
+        let accrued_fees = mine_account.accrued_fees;
+        let mut remaining_fees = accrued_fees;
+        let last = fee_splits.len() - 1;
+        for (i, (split, recipient)) in fee_splits
+            .iter()
+            .zip(ctx.remaining_accounts.iter())
+            .enumerate()
+        {
+            if *recipient.key != split.recipient {
+                return Err(ErrorCode::InvalidFeeSplits.into());
+            }
+
+            let amount: u64 = if i == last {
+                remaining_fees
+            } else {
+                (accrued_fees as u128)
+                    .checked_mul(split.share as u128)
+                    .unwrap()
+                    .checked_div(FEE_MULTIPLIER as u128)
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            };
+            remaining_fees -= amount;
+
+            if amount > 0 {
+                spl_token_transfer(TokenTransferParams {
+                    source: aury_vault.to_account_info(),
+                    mint: aury_mint.to_account_info(),
+                    decimals: aury_mint.decimals,
+                    destination: recipient.clone(),
+                    amount,
+                    authority: aury_vault.to_account_info(),
+                    authority_signer_seeds: aury_vault_account_signer,
+                    token_program: token_program.to_account_info(),
+                })?;
+            }
+        }
+
+        mine_account.accrued_fees = 0;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.mine_account.assert_owner(&ctx.accounts.owner))]
+    pub fn withdraw_mine_fees(
+        ctx: Context<WithdrawMineFees>,
+        nonce_aury_vault: u8,
+    ) -> ProgramResult {
+        let mine_account = &mut ctx.accounts.mine_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+        let aury_vault = &mut ctx.accounts.aury_vault;
+        let aury_to = &mut ctx.accounts.aury_to;
+        let token_program = &ctx.accounts.token_program;
+
+        // split fees can only be distributed to their recipients
+        if !ctx.accounts.mine_fees_account.fee_splits.is_empty() {
+            return Err(ErrorCode::MineFeesSplit.into());
+        }
+
+        // compute aury vault account signer seeds
+        let aury_mint_key = aury_mint.key();
+        let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
+        let aury_vault_account_signer = &aury_vault_account_seeds[..];
+
+        // transfer the accrued fees to the owner
+        if mine_account.accrued_fees > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: aury_to.to_account_info(),
+                amount: mine_account.accrued_fees,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        mine_account.accrued_fees = 0;
+
+        Ok(())
+    }
+
+    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
+    pub fn reward_to_mine(
+        ctx: Context<RewardToMine>,
+        _nonce_config: u8,
+        _nonce_aury_vault: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        let mine_account = &mut ctx.accounts.mine_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+        let aury_vault = &mut ctx.accounts.aury_vault;
+        let aury_from = &mut ctx.accounts.aury_from;
+        let admin = &ctx.accounts.admin;
+        let token_program = &ctx.accounts.token_program;
+
+        // transfer aury to the vault
+        spl_token_transfer(TokenTransferParams {
+            source: aury_from.to_account_info(),
+            mint: aury_mint.to_account_info(),
+            decimals: aury_mint.decimals,
+            destination: aury_vault.to_account_info(),
+            amount: amount,
+            authority: admin.to_account_info(),
+            authority_signer_seeds: &[],
+            token_program: token_program.to_account_info(),
+        })?;
+
+        // hold rewards back while nobody mines, so the next miner to join
+        // doesn't capture them
+        if mine_account.x_total_amount == 0 {
+            mine_account.pending_rewards += amount;
+            return Ok(());
+        }
+
+        // update mine_account info, releasing any pending rewards
+        mine_account.total_amount += amount + mine_account.pending_rewards;
+        mine_account.pending_rewards = 0;
+
+        // update mine_account shares
+        if mine_account.shares.len() == constants::SHARES_LIMIT {
+            mine_account.shares.remove(0);
+        }
+        let aury_share = AuryShare {
+            timestamp: Clock::get().unwrap().unix_timestamp as u64,
+            token_amount: mine_account.total_amount,
+            x_token_amount: mine_account.x_total_amount,
+        };
+        mine_account.shares.push(aury_share);
+
+        Ok(())
+    }
+
+    pub fn add_miners_to_mine(
+        ctx: Context<AddMinersToMine>,
+        _nonce_user_miner: u8,
+        min_x_shares_out: u64,
+    ) -> ProgramResult {
+        let mine_account = &mut ctx.accounts.mine_account;
+        let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let owner = &ctx.accounts.owner;
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+
+        // remaining accounts: the mine's access proofs, the position nft proof
+        // of user_miner_account, then any further user miner accounts, each
+        // followed by its own position nft proof
+        let mine_key = mine_account.key();
+        let mut proofs_used =
+            mine_account.assert_member(mine_key, owner.key, ctx.remaining_accounts)?;
+        user_miner_account.assert_holder(owner.key, &ctx.remaining_accounts[proofs_used..])?;
+        proofs_used += user_miner_account.holder_proofs();
+
+        let total_amount = mine_account.total_amount;
+        let x_total_amount = mine_account.x_total_amount;
+        let mut power = user_miner_account.power;
+        mine_account.join(
+            mine_key,
+            &ctx.accounts.mine_fees_account,
+            user_miner_account,
+            now,
+        )?;
+
+        let mut added = vec![user_miner_account.key()];
+        let mut remaining_accounts = &ctx.remaining_accounts[proofs_used..];
+        while let Some((user_miner_info, proofs)) = remaining_accounts.split_first() {
+            if !user_miner_info.is_writable || added.contains(user_miner_info.key) {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+
+            let mut user_miner = Account::<UserMinerAccount>::try_from(user_miner_info)?;
+            let (user_miner_key, _) = Pubkey::find_program_address(
+                &[
+                    user_miner.miner_type.as_ref(),
+                    constants::MINER_PDA_SEED.as_ref(),
+                    user_miner.seed_key().as_ref(),
+                ],
+                ctx.program_id,
+            );
+            if *user_miner_info.key != user_miner_key {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+            user_miner.assert_holder(owner.key, proofs)?;
+
+            power += user_miner.power;
+            mine_account.join(
+                mine_key,
+                &ctx.accounts.mine_fees_account,
+                &mut user_miner,
+                now,
+            )?;
+            write_account(user_miner_info, &*user_miner)?;
+
+            added.push(*user_miner_info.key);
+            remaining_accounts = &proofs[user_miner.holder_proofs()..];
+        }
+
+        let x_shares = mine_account.x_total_amount - x_total_amount;
+        if x_shares < min_x_shares_out {
+            return Err(ErrorCode::XSharesBelowMin.into());
+        }
+
+        emit!(MinersAddedToMine {
+            mine: mine_key,
+            owner: *owner.key,
+            miners: added.len() as u64,
+            power,
+            mining_power: mine_account.total_amount - total_amount,
+            x_shares,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_miner<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimMiner<'info>>,
+        _nonce_config: u8,
+        _nonce_user_miner: u8,
+        nonce_aury_vault: u8,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let mine_account = &mut ctx.accounts.mine_account;
+        let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let aury_vault = &mut ctx.accounts.aury_vault;
+        let aury_to = &mut ctx.accounts.aury_to;
+        let treasury = &mut ctx.accounts.treasury;
+        let aury_to_authority = &ctx.accounts.aury_to_authority;
+        let token_program = &ctx.accounts.token_program;
+
+        user_miner_account.assert_holder(aury_to_authority.key, ctx.remaining_accounts)?;
+        user_miner_account.assert_claimable(mine_account.key())?;
+
+        // determine user reward amount
+        let what = mine_account.reward_for(user_miner_account);
+
+        // compute aury vault account signer seeds
+        let aury_mint = &ctx.accounts.aury_mint;
+        let aury_mint_key = aury_mint.key();
+        let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
+        let aury_vault_account_signer = &aury_vault_account_seeds[..];
+
+        // transfer aury to the user
+        let (reward_amount, protocol_fee_amount, mine_fee_amount) = split_reward(
+            what,
+            config_account.clamp_mine_fee(
+                user_miner_account.effective_fee(mine_account, &ctx.accounts.mine_fees_account),
+            ),
+            config_account.protocol_fee,
+        );
+        spl_token_transfer(TokenTransferParams {
+            source: aury_vault.to_account_info(),
+            mint: aury_mint.to_account_info(),
+            decimals: aury_mint.decimals,
+            destination: aury_to.to_account_info(),
+            amount: user_miner_account.power + reward_amount,
+            authority: aury_vault.to_account_info(),
+            authority_signer_seeds: aury_vault_account_signer,
+            token_program: token_program.to_account_info(),
+        })?;
+
+        // transfer the protocol's cut of the fee to the treasury
+        if protocol_fee_amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: treasury.to_account_info(),
+                amount: protocol_fee_amount,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // accrue the mine fee in the vault for the owner to withdraw
+        mine_account.accrued_fees += mine_fee_amount;
+
+        // update mine_account
+        mine_account.leave(user_miner_account, what);
+
+        // burn the position nft along with the miner
+        if user_miner_account.position_mint != Pubkey::default() {
+            let position_mint = ctx
+                .remaining_accounts
+                .get(1)
+                .ok_or(ErrorCode::InvalidAccounts)?;
+            if *position_mint.key != user_miner_account.position_mint {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+
+            spl_token_burn(TokenBurnParams {
+                mint: position_mint.clone(),
+                source: ctx.remaining_accounts[0].clone(),
+                amount: 1,
+                authority: aury_to_authority.to_account_info(),
+                authority_signer_seeds: &[],
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn update_auto_claim<'info>(
+        ctx: Context<'_, '_, '_, 'info, UpdateAutoClaim<'info>>,
+        _nonce_config: u8,
+        _nonce_user_miner: u8,
+        auto_claim: bool,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let owner = &ctx.accounts.owner;
+        let token_program = &ctx.accounts.token_program;
+
+        // remaining accounts: the position nft proof
+        user_miner_account.assert_holder(owner.key, ctx.remaining_accounts)?;
+
+        // keepers burn the position nft when they claim the miner, so the
+        // holder lets the config account burn it while opted in
+        if user_miner_account.position_mint != Pubkey::default() {
+            let position_token_account = ctx.remaining_accounts[0].clone();
+            if auto_claim {
+                spl_token_approve(TokenApproveParams {
+                    source: position_token_account,
+                    delegate: config_account.to_account_info(),
+                    amount: 1,
+                    authority: owner.to_account_info(),
+                    token_program: token_program.to_account_info(),
+                })?;
+            } else {
+                spl_token_revoke(TokenRevokeParams {
+                    source: position_token_account,
+                    authority: owner.to_account_info(),
+                    token_program: token_program.to_account_info(),
+                })?;
+            }
+        }
+
+        user_miner_account.auto_claim = auto_claim;
+
+        Ok(())
+    }
+
+    pub fn auto_claim_miner<'info>(
+        ctx: Context<'_, '_, '_, 'info, AutoClaimMiner<'info>>,
+        nonce_config: u8,
+        _nonce_user_miner: u8,
+        nonce_aury_vault: u8,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let mine_account = &mut ctx.accounts.mine_account;
+        let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+        let aury_vault = &mut ctx.accounts.aury_vault;
+        let aury_to = &mut ctx.accounts.aury_to;
+        let treasury = &mut ctx.accounts.treasury;
+        let keeper_to = &mut ctx.accounts.keeper_to;
+        let owner = &ctx.accounts.owner;
+        let token_program = &ctx.accounts.token_program;
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+
+        user_miner_account.assert_claimable(mine_account.key())?;
+        if !user_miner_account.auto_claim
+            || now < user_miner_account.mining_start_at + user_miner_account.duration
+        {
+            return Err(ErrorCode::AutoClaimUnavailable.into());
+        }
+
+        // remaining accounts: the position nft proof, then its mint
+        let holder = user_miner_account.holder(ctx.remaining_accounts)?;
+        if !(aury_to.owner == holder && *owner.key == holder) {
+            return Err(ErrorCode::InvalidAccounts.into());
+        }
+
+        // determine user reward amount
+        let what = mine_account.reward_for(user_miner_account);
+
+        // compute aury vault account signer seeds
+        let aury_mint_key = aury_mint.key();
+        let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
+        let aury_vault_account_signer = &aury_vault_account_seeds[..];
+
+        // the keeper's tip comes out of the user's reward
+        let (reward_amount, protocol_fee_amount, mine_fee_amount) = split_reward(
+            what,
+            config_account.clamp_mine_fee(
+                user_miner_account.effective_fee(mine_account, &ctx.accounts.mine_fees_account),
+            ),
+            config_account.protocol_fee,
+        );
+        let tip_amount: u64 = (reward_amount as u128)
+            .checked_mul(config_account.keeper_tip as u128)
             .unwrap()
             .checked_div(FEE_MULTIPLIER as u128)
             .unwrap()
             .try_into()
             .unwrap();
+
+        // transfer aury to the owner
         spl_token_transfer(TokenTransferParams {
             source: aury_vault.to_account_info(),
+            mint: aury_mint.to_account_info(),
+            decimals: aury_mint.decimals,
             destination: aury_to.to_account_info(),
-            amount: user_miner_account.power + reward_amount,
+            amount: user_miner_account.power + reward_amount - tip_amount,
+            authority: aury_vault.to_account_info(),
+            authority_signer_seeds: aury_vault_account_signer,
+            token_program: token_program.to_account_info(),
+        })?;
+
+        // transfer the tip to the keeper
+        if tip_amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: keeper_to.to_account_info(),
+                amount: tip_amount,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // transfer the protocol's cut of the fee to the treasury
+        if protocol_fee_amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: treasury.to_account_info(),
+                amount: protocol_fee_amount,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // accrue the mine fee in the vault for the owner to withdraw
+        mine_account.accrued_fees += mine_fee_amount;
+
+        // update mine_account
+        mine_account.leave(user_miner_account, what);
+
+        // burn the position nft along with the miner, as the config account
+        // the holder delegated it to when opting in
+        if user_miner_account.position_mint != Pubkey::default() {
+            let position_token_account = &ctx.remaining_accounts[0];
+            let position_mint = ctx
+                .remaining_accounts
+                .get(1)
+                .ok_or(ErrorCode::InvalidAccounts)?;
+            if *position_mint.key != user_miner_account.position_mint {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+
+            let token_account = Account::<TokenAccount>::try_from(position_token_account)?;
+            if token_account.delegate != COption::Some(config_account.key()) {
+                return Err(ErrorCode::AutoClaimUnavailable.into());
+            }
+
+            let config_account_seeds = &[constants::CONFIG_PDA_SEED.as_ref(), &[nonce_config]];
+            let config_account_signer = &config_account_seeds[..];
+            spl_token_burn(TokenBurnParams {
+                mint: position_mint.clone(),
+                source: position_token_account.clone(),
+                amount: 1,
+                authority: config_account.to_account_info(),
+                authority_signer_seeds: config_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    pub fn claim_many<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimMany<'info>>,
+        _nonce_config: u8,
+        nonce_aury_vault: u8,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let mine_account = &mut ctx.accounts.mine_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+        let aury_vault = &mut ctx.accounts.aury_vault;
+        let aury_to = &mut ctx.accounts.aury_to;
+        let treasury = &mut ctx.accounts.treasury;
+        let aury_to_authority = &ctx.accounts.aury_to_authority;
+        let token_program = &ctx.accounts.token_program;
+
+        // remaining accounts: the user miner accounts, each followed by its
+        // position token account and mint when minted as an nft
+        let mine_key = mine_account.key();
+        let mut claimed: Vec<Pubkey> = vec![];
+        let mut user_amount: u64 = 0;
+        let mut protocol_amount: u64 = 0;
+        let mut fee_amount: u64 = 0;
+
+        let mut remaining_accounts = ctx.remaining_accounts;
+        while let Some((user_miner_info, proofs)) = remaining_accounts.split_first() {
+            if !user_miner_info.is_writable || claimed.contains(user_miner_info.key) {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+
+            let user_miner = Account::<UserMinerAccount>::try_from(user_miner_info)?;
+            let (user_miner_key, _) = Pubkey::find_program_address(
+                &[
+                    user_miner.miner_type.as_ref(),
+                    constants::MINER_PDA_SEED.as_ref(),
+                    user_miner.seed_key().as_ref(),
+                ],
+                ctx.program_id,
+            );
+            if *user_miner_info.key != user_miner_key {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+            user_miner.assert_holder(aury_to_authority.key, proofs)?;
+            user_miner.assert_claimable(mine_key)?;
+
+            // determine user reward amount
+            let what = mine_account.reward_for(&user_miner);
+            let (reward_amount, protocol_fee_amount, mine_fee_amount) = split_reward(
+                what,
+                config_account.clamp_mine_fee(
+                    user_miner.effective_fee(mine_account, &ctx.accounts.mine_fees_account),
+                ),
+                config_account.protocol_fee,
+            );
+            user_amount += user_miner.power + reward_amount;
+            protocol_amount += protocol_fee_amount;
+            fee_amount += mine_fee_amount;
+
+            // update mine_account
+            mine_account.leave(&user_miner, what);
+
+            // burn the position nft along with the miner
+            let mut proofs_used = 0;
+            if user_miner.position_mint != Pubkey::default() {
+                let position_mint = proofs.get(1).ok_or(ErrorCode::InvalidAccounts)?;
+                if *position_mint.key != user_miner.position_mint {
+                    return Err(ErrorCode::InvalidAccounts.into());
+                }
+
+                spl_token_burn(TokenBurnParams {
+                    mint: position_mint.clone(),
+                    source: proofs[0].clone(),
+                    amount: 1,
+                    authority: aury_to_authority.to_account_info(),
+                    authority_signer_seeds: &[],
+                    token_program: token_program.to_account_info(),
+                })?;
+                proofs_used = 2;
+            }
+
+            close_account(user_miner_info, &aury_to_authority.to_account_info())?;
+
+            claimed.push(*user_miner_info.key);
+            remaining_accounts = &proofs[proofs_used..];
+        }
+
+        if claimed.is_empty() {
+            return Err(ErrorCode::InvalidAccounts.into());
+        }
+
+        // compute aury vault account signer seeds
+        let aury_mint_key = aury_mint.key();
+        let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
+        let aury_vault_account_signer = &aury_vault_account_seeds[..];
+
+        // transfer aury to the user
+        spl_token_transfer(TokenTransferParams {
+            source: aury_vault.to_account_info(),
+            mint: aury_mint.to_account_info(),
+            decimals: aury_mint.decimals,
+            destination: aury_to.to_account_info(),
+            amount: user_amount,
             authority: aury_vault.to_account_info(),
             authority_signer_seeds: aury_vault_account_signer,
             token_program: token_program.to_account_info(),
         })?;
 
-        // transfer aury fee
-        let fee_amount = what - reward_amount;
-        if fee_amount > 0 {
-            spl_token_transfer(TokenTransferParams {
-                source: aury_vault.to_account_info(),
-                destination: fee_to.to_account_info(),
-                amount: fee_amount,
-                authority: aury_vault.to_account_info(),
-                authority_signer_seeds: aury_vault_account_signer,
-                token_program: token_program.to_account_info(),
-            })?;
-        }
+        // transfer the protocol's cut of the fee to the treasury
+        if protocol_amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: treasury.to_account_info(),
+                amount: protocol_amount,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // accrue the mine fee in the vault for the owner to withdraw
+        mine_account.accrued_fees += fee_amount;
+
+        Ok(())
+    }
+
+    pub fn harvest(
+        ctx: Context<Harvest>,
+        _nonce_config: u8,
+        _nonce_user_miner: u8,
+        nonce_aury_vault: u8,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let mine_account = &mut ctx.accounts.mine_account;
+        let user_miner_account = &mut ctx.accounts.user_miner_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+        let aury_vault = &mut ctx.accounts.aury_vault;
+        let aury_to = &mut ctx.accounts.aury_to;
+        let treasury = &mut ctx.accounts.treasury;
+        let aury_to_authority = &ctx.accounts.aury_to_authority;
+        let token_program = &ctx.accounts.token_program;
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+
+        user_miner_account.assert_holder(aury_to_authority.key, ctx.remaining_accounts)?;
+        user_miner_account.assert_claimable(mine_account.key())?;
+
+        // determine the gains since the last harvest
+        let (what, x_what) = mine_account.harvestable(user_miner_account, now);
+        if what == 0 {
+            return Err(ErrorCode::NothingToHarvest.into());
+        }
+
+        // compute aury vault account signer seeds
+        let aury_mint_key = aury_mint.key();
+        let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
+        let aury_vault_account_signer = &aury_vault_account_seeds[..];
+
+        // transfer aury to the user
+        let (reward_amount, protocol_fee_amount, mine_fee_amount) = split_reward(
+            what,
+            config_account.clamp_mine_fee(
+                user_miner_account.effective_fee(mine_account, &ctx.accounts.mine_fees_account),
+            ),
+            config_account.protocol_fee,
+        );
+        spl_token_transfer(TokenTransferParams {
+            source: aury_vault.to_account_info(),
+            mint: aury_mint.to_account_info(),
+            decimals: aury_mint.decimals,
+            destination: aury_to.to_account_info(),
+            amount: reward_amount,
+            authority: aury_vault.to_account_info(),
+            authority_signer_seeds: aury_vault_account_signer,
+            token_program: token_program.to_account_info(),
+        })?;
+
+        // transfer the protocol's cut of the fee to the treasury
+        if protocol_fee_amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: treasury.to_account_info(),
+                amount: protocol_fee_amount,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // accrue the mine fee in the vault for the owner to withdraw
+        mine_account.accrued_fees += mine_fee_amount;
+
+        // burn the x-shares the gains were worth, the principal keeps mining
+        mine_account.total_amount = mine_account.total_amount.checked_sub(what).unwrap();
+        mine_account.x_total_amount -= x_what;
+        user_miner_account.x_aury_amount -= x_what;
+        user_miner_account.last_harvested_at = now;
+
+        Ok(())
+    }
+
+    pub fn restake_miner<'info>(
+        ctx: Context<'_, '_, '_, 'info, RestakeMiner<'info>>,
+        nonce_config: u8,
+        nonce_user_miner: u8,
+        nonce_new_user_miner: u8,
+        nonce_aury_vault: u8,
+        _nonce_wallet_purchase: u8,
+        min_amount: u64,
+        min_x_shares_out: u64,
+    ) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let mine_account = &mut ctx.accounts.mine_account;
+        let user_miner_info = &ctx.accounts.user_miner_account;
+        let new_miner_account = &mut ctx.accounts.new_miner_account;
+        let wallet_purchase_account = &mut ctx.accounts.wallet_purchase_account;
+        let aury_mint = &ctx.accounts.aury_mint;
+        let aury_vault = &mut ctx.accounts.aury_vault;
+        let aury_to = &mut ctx.accounts.aury_to;
+        let treasury = &mut ctx.accounts.treasury;
+        let owner = &ctx.accounts.owner;
+        let system_program = &ctx.accounts.system_program;
+        let token_program = &ctx.accounts.token_program;
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+
+        // the position is loaded by hand, as it may be closed below
+        let user_miner_account = &Account::<UserMinerAccount>::try_from(user_miner_info)?;
+        let user_miner_seed_key = user_miner_account.seed_key();
+        let user_miner_key = Pubkey::create_program_address(
+            &[
+                user_miner_account.miner_type.as_ref(),
+                constants::MINER_PDA_SEED.as_ref(),
+                user_miner_seed_key.as_ref(),
+                &[nonce_user_miner],
+            ],
+            ctx.program_id,
+        )
+        .map_err(|_| ErrorCode::InvalidAccounts)?;
+        if *user_miner_info.key != user_miner_key {
+            return Err(ErrorCode::InvalidAccounts.into());
+        }
+
+        // remaining accounts: the position nft proof and metadata, the new
+        // miner's collection proofs, then the new mine's access proofs
+        user_miner_account.assert_holder(owner.key, ctx.remaining_accounts)?;
+        user_miner_account.assert_claimable(mine_account.key())?;
+        if now < user_miner_account.mining_start_at + user_miner_account.duration {
+            return Err(ErrorCode::ClaimUnavailable.into());
+        }
+        let mut proofs_used = if user_miner_account.position_mint != Pubkey::default() {
+            2
+        } else {
+            0
+        };
+
+        new_miner_account.assert_buyer(owner.key, &ctx.remaining_accounts[proofs_used..])?;
+        if new_miner_account.required_collection != Pubkey::default() {
+            proofs_used += 2;
+        }
+
+        // determine user reward amount
+        let what = mine_account.reward_for(user_miner_account);
+
+        // compute aury vault account signer seeds
+        let aury_mint_key = aury_mint.key();
+        let aury_vault_account_seeds = &[aury_mint_key.as_ref(), &[nonce_aury_vault]];
+        let aury_vault_account_signer = &aury_vault_account_seeds[..];
+
+        // the fees leave the vault, the power and net reward are restaked
+        let (reward_amount, protocol_fee_amount, mine_fee_amount) = split_reward(
+            what,
+            config_account.clamp_mine_fee(
+                user_miner_account.effective_fee(mine_account, &ctx.accounts.mine_fees_account),
+            ),
+            config_account.protocol_fee,
+        );
+
+        // transfer the protocol's cut of the fee to the treasury
+        if protocol_fee_amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: treasury.to_account_info(),
+                amount: protocol_fee_amount,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // accrue the mine fee in the vault for the owner to withdraw
+        mine_account.accrued_fees += mine_fee_amount;
+
+        // take the miner out of its mine
+        mine_account.leave(user_miner_account, what);
+
+        // the position nft carries over to the new position, renamed after
+        // its new miner type
+        if user_miner_account.position_mint != Pubkey::default()
+            && new_miner_account.key() != user_miner_account.miner_type
+        {
+            let metadata = ctx
+                .remaining_accounts
+                .get(1)
+                .ok_or(ErrorCode::InvalidAccounts)?;
+            let config_account_seeds = &[constants::CONFIG_PDA_SEED.as_ref(), &[nonce_config]];
+            let config_account_signer = &config_account_seeds[..];
+            update_position_nft(
+                &user_miner_account.position_mint,
+                metadata,
+                &new_miner_account.name,
+                &new_miner_account.uri,
+                &config_account.to_account_info(),
+                config_account_signer,
+                &ctx.accounts.token_metadata_program,
+            )?;
+        }
+
+        // as many new miners as the restaked aury buys at their current
+        // price, only what is left over is paid out to the holder
+        let restaked_amount = user_miner_account.power + reward_amount;
+        let amount = new_miner_account.amount_for(restaked_amount)?;
+        if amount == 0 || amount < min_amount {
+            return Err(ErrorCode::MinerPriceAboveMax.into());
+        }
+        let power = new_miner_account.price_for(amount)?;
+
+        if restaked_amount > power {
+            spl_token_transfer(TokenTransferParams {
+                source: aury_vault.to_account_info(),
+                mint: aury_mint.to_account_info(),
+                decimals: aury_mint.decimals,
+                destination: aury_to.to_account_info(),
+                amount: restaked_amount - power,
+                authority: aury_vault.to_account_info(),
+                authority_signer_seeds: aury_vault_account_signer,
+                token_program: token_program.to_account_info(),
+            })?;
+        }
+
+        // a new miner type is bought from its supply and recorded against the
+        // holder's wallet cap, as the holder is who bought it. Restaking into
+        // the same type rolls the position over without a new sale
+        if new_miner_account.key() != user_miner_account.miner_type {
+            new_miner_account.assert_purchasable(amount, wallet_purchase_account.purchased)?;
+            new_miner_account.total_purchased += amount;
+            wallet_purchase_account.record(new_miner_account.key(), *owner.key, amount);
+        }
+
+        // open the new position and put it to work in the new mine, which
+        // may be the mine the miner just left
+        let mut new_user_miner = UserMinerAccount {
+            version: UserMinerAccount::VERSION,
+            owner: *owner.key,
+            miner_type: new_miner_account.key(),
+            power,
+            duration: new_miner_account.duration,
+            power_multiplier: new_miner_account.power_multiplier,
+            amount,
+            position_mint: user_miner_account.position_mint,
+            ..Default::default()
+        };
+
+        let new_mine_key = ctx.accounts.new_mine_account.key();
+        let mut new_mine_account = if new_mine_key == mine_account.key() {
+            None
+        } else {
+            Some(Account::<MineAccount>::try_from(
+                &ctx.accounts.new_mine_account,
+            )?)
+        };
+        let target_mine: &mut MineAccount = match new_mine_account.as_mut() {
+            Some(new_mine_account) => new_mine_account,
+            None => mine_account,
+        };
+        target_mine.assert_member(
+            new_mine_key,
+            owner.key,
+            &ctx.remaining_accounts[proofs_used..],
+        )?;
+        let x_total_amount = target_mine.x_total_amount;
+        target_mine.join(
+            new_mine_key,
+            &ctx.accounts.new_mine_fees_account,
+            &mut new_user_miner,
+            now,
+        )?;
+
+        let x_shares = target_mine.x_total_amount - x_total_amount;
+        if x_shares < min_x_shares_out {
+            return Err(ErrorCode::XSharesBelowMin.into());
+        }
+        if let Some(new_mine_account) = &new_mine_account {
+            write_account(&ctx.accounts.new_mine_account, &**new_mine_account)?;
+        }
+
+        // restaking into the same miner type reuses the position account,
+        // otherwise the new one is created and the old one closed
+        let new_user_miner_account = &ctx.accounts.new_user_miner_account;
+        if new_user_miner.miner_type == user_miner_account.miner_type {
+            if *new_user_miner_account.key != *user_miner_info.key {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+
+            write_account(user_miner_info, &new_user_miner)?;
+        } else {
+            let new_seed_key = new_user_miner.seed_key();
+            let new_user_miner_seeds = &[
+                new_user_miner.miner_type.as_ref(),
+                constants::MINER_PDA_SEED.as_ref(),
+                new_seed_key.as_ref(),
+                &[nonce_new_user_miner],
+            ];
+            let new_user_miner_key =
+                Pubkey::create_program_address(new_user_miner_seeds, ctx.program_id)
+                    .map_err(|_| ErrorCode::InvalidAccounts)?;
+            if *new_user_miner_account.key != new_user_miner_key {
+                return Err(ErrorCode::InvalidAccounts.into());
+            }
+
+            create_program_account(
+                new_user_miner_account,
+                &owner.to_account_info(),
+                &system_program.to_account_info(),
+                new_user_miner_seeds,
+                UserMinerAccount::LEN,
+                ctx.program_id,
+            )?;
+            write_account(new_user_miner_account, &new_user_miner)?;
+
+            close_account(user_miner_info, &owner.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn migrate_config(ctx: Context<MigrateConfig>, _nonce_config: u8) -> ProgramResult {
+        let config_account = &ctx.accounts.config_account;
+        let admin = &ctx.accounts.admin;
+        let system_program = &ctx.accounts.system_program;
+
+        assert_program_account::<ConfigAccount>(config_account, ctx.program_id)?;
+
+        // grow the account to the current layout, then rewrite it
+        let original_len = config_account.data_len();
+        realloc_account(
+            config_account,
+            &admin.to_account_info(),
+            &system_program.to_account_info(),
+            ConfigAccount::LEN,
+        )?;
+        let migrated = ConfigAccount::upgrade(&config_account.try_borrow_data()?, original_len)?;
+        migrated.assert_admin(admin)?;
+
+        write_account(config_account, &migrated)
+    }
+
+    #[access_control(ctx.accounts.config_account.assert_admin(&ctx.accounts.admin))]
+    pub fn migrate_miner(ctx: Context<MigrateMiner>, _nonce_config: u8) -> ProgramResult {
+        let miner_account = &ctx.accounts.miner_account;
+        let admin = &ctx.accounts.admin;
+        let system_program = &ctx.accounts.system_program;
+
+        assert_program_account::<MinerAccount>(miner_account, ctx.program_id)?;
+
+        // grow the account to the current layout, then rewrite it
+        let original_len = miner_account.data_len();
+        realloc_account(
+            miner_account,
+            &admin.to_account_info(),
+            &system_program.to_account_info(),
+            MinerAccount::LEN,
+        )?;
+        let migrated = MinerAccount::upgrade(&miner_account.try_borrow_data()?, original_len)?;
+
+        write_account(miner_account, &migrated)
+    }
+
+    pub fn migrate_mine(ctx: Context<MigrateMine>, _nonce_mine_fees: u8) -> ProgramResult {
+        let mine_account = &ctx.accounts.mine_account;
+        let mine_fees_account = &mut ctx.accounts.mine_fees_account;
+        let payer = &ctx.accounts.payer;
+        let system_program = &ctx.accounts.system_program;
+
+        // anyone may migrate a mine, only its layout changes and the caller
+        // pays for it
+        assert_program_account::<MineAccount>(mine_account, ctx.program_id)?;
+
+        // grow the account to the current layout, then rewrite it
+        let original_len = mine_account.data_len();
+        realloc_account(
+            mine_account,
+            &payer.to_account_info(),
+            &system_program.to_account_info(),
+            MineAccount::LEN,
+        )?;
+        let migrated = MineAccount::upgrade(&mine_account.try_borrow_data()?, original_len)?;
+
+        // mines from before fee tiers get an empty fee schedule
+        if mine_fees_account.version == 0 {
+            mine_fees_account.version = MineFeesAccount::VERSION;
+            mine_fees_account.mine = mine_account.key();
+        }
+
+        write_account(mine_account, &migrated)
+    }
+
+    pub fn migrate_user_miner(ctx: Context<MigrateUserMiner>) -> ProgramResult {
+        let user_miner_account = &ctx.accounts.user_miner_account;
+        let payer = &ctx.accounts.payer;
+        let system_program = &ctx.accounts.system_program;
+
+        // anyone may migrate a position, only its layout changes and the
+        // caller pays for it. Traded positions can't rely on their buyer
+        assert_program_account::<UserMinerAccount>(user_miner_account, ctx.program_id)?;
+
+        // grow the account to the current layout, then rewrite it
+        let original_len = user_miner_account.data_len();
+        realloc_account(
+            user_miner_account,
+            &payer.to_account_info(),
+            &system_program.to_account_info(),
+            UserMinerAccount::LEN,
+        )?;
+        let migrated =
+            UserMinerAccount::upgrade(&user_miner_account.try_borrow_data()?, original_len)?;
+
+        write_account(user_miner_account, &migrated)
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_aury_vault: u8)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = initializer,
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        space = ConfigAccount::LEN,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = initializer,
+        token::mint = aury_mint,
+        token::authority = aury_vault,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = _nonce_aury_vault,
+    )]
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub initializer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_pending_config_change: u8, change: ConfigChange)]
+pub struct QueueConfigChange<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    // one pending change per kind, so an urgent one needn't wait on another
+    #[account(
+        init,
+        payer = admin,
+        seeds = [ constants::PENDING_CONFIG_CHANGE_PDA_SEED.as_ref(), &[change.kind()] ],
+        bump = _nonce_pending_config_change,
+        space = PendingConfigChange::LEN,
+    )]
+    pub pending_config_change: Box<Account<'info, PendingConfigChange>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_pending_config_change: u8)]
+pub struct ExecuteConfigChange<'info> {
+    #[account(
+        mut,
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [
+            constants::PENDING_CONFIG_CHANGE_PDA_SEED.as_ref(),
+            &[pending_config_change.change.kind()],
+        ],
+        bump = _nonce_pending_config_change,
+    )]
+    pub pending_config_change: Box<Account<'info, PendingConfigChange>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_pending_config_change: u8)]
+pub struct CancelConfigChange<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [
+            constants::PENDING_CONFIG_CHANGE_PDA_SEED.as_ref(),
+            &[pending_config_change.change.kind()],
+        ],
+        bump = _nonce_pending_config_change,
+    )]
+    pub pending_config_change: Box<Account<'info, PendingConfigChange>>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _miner_created_at: u64, _nonce_miner: u8)]
+pub struct CreateMiner<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [ _miner_created_at.to_string().as_ref(), constants::MINER_PDA_SEED.as_ref() ],
+        bump = _nonce_miner,
+        space = MinerAccount::LEN,
+    )]
+    pub miner_account: Box<Account<'info, MinerAccount>>,
+
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8)]
+pub struct FreezeMiner<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(mut)]
+    pub miner_account: Box<Account<'info, MinerAccount>>,
+
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8)]
+pub struct UpdateMiner<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(mut)]
+    pub miner_account: Box<Account<'info, MinerAccount>>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8)]
+pub struct RemoveMiner<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        mut,
+        close = admin,
+    )]
+    pub miner_account: Box<Account<'info, MinerAccount>>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce_config: u8, _nonce_user_miner: u8, _nonce_aury_vault: u8, _nonce_wallet_purchase: u8)]
+pub struct PurchaseMiner<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(mut)]
+    pub miner_account: Box<Account<'info, MinerAccount>>,
+
+    #[account(
+        init,
+        payer = aury_from_authority,
+        mint::decimals = 0,
+        mint::authority = config_account,
+    )]
+    pub position_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = aury_from_authority,
+        seeds = [ miner_account.key().as_ref(), constants::MINER_PDA_SEED.as_ref(), position_mint.key().as_ref() ],
+        bump = _nonce_user_miner,
+        space = UserMinerAccount::LEN,
+    )]
+    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = aury_from_authority,
+        seeds = [ miner_account.key().as_ref(), constants::WALLET_PURCHASE_PDA_SEED.as_ref(), aury_from.owner.as_ref() ],
+        bump = _nonce_wallet_purchase,
+        space = WalletPurchaseAccount::LEN,
+    )]
+    pub wallet_purchase_account: Box<Account<'info, WalletPurchaseAccount>>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = _nonce_aury_vault,
+    )]
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub aury_from: Box<Account<'info, TokenAccount>>,
+
+    // the owner of aury_from, or a delegate buying on the owner's behalf
+    #[account(mut)]
+    pub aury_from_authority: Signer<'info>,
+
+    // the owner of aury_from, who receives the position nft
+    #[account(
+        constraint = aury_from_owner.key() == aury_from.owner @ ErrorCode::InvalidAccounts
+    )]
+    pub aury_from_owner: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = aury_from_authority,
+        associated_token::mint = position_mint,
+        associated_token::authority = aury_from_owner,
+    )]
+    pub position_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub metadata: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub master_edition: AccountInfo<'info>,
+
+    #[account(address = mpl_token_metadata::id())]
+    pub token_metadata_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce_config: u8, _nonce_user_miner: u8, _nonce_aury_vault: u8, _nonce_wallet_purchase: u8)]
+pub struct PurchaseAndMine<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(mut)]
+    pub miner_account: Box<Account<'info, MinerAccount>>,
+
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    #[account(
+        init,
+        payer = aury_from_authority,
+        mint::decimals = 0,
+        mint::authority = config_account,
+    )]
+    pub position_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = aury_from_authority,
+        seeds = [ miner_account.key().as_ref(), constants::MINER_PDA_SEED.as_ref(), position_mint.key().as_ref() ],
+        bump = _nonce_user_miner,
+        space = UserMinerAccount::LEN,
+    )]
+    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = aury_from_authority,
+        seeds = [ miner_account.key().as_ref(), constants::WALLET_PURCHASE_PDA_SEED.as_ref(), aury_from.owner.as_ref() ],
+        bump = _nonce_wallet_purchase,
+        space = WalletPurchaseAccount::LEN,
+    )]
+    pub wallet_purchase_account: Box<Account<'info, WalletPurchaseAccount>>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = _nonce_aury_vault,
+    )]
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub aury_from: Box<Account<'info, TokenAccount>>,
+
+    // the owner of aury_from, or a delegate buying on the owner's behalf
+    #[account(mut)]
+    pub aury_from_authority: Signer<'info>,
+
+    // the owner of aury_from, who receives the position nft
+    #[account(
+        constraint = aury_from_owner.key() == aury_from.owner @ ErrorCode::InvalidAccounts
+    )]
+    pub aury_from_owner: AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = aury_from_authority,
+        associated_token::mint = position_mint,
+        associated_token::authority = aury_from_owner,
+    )]
+    pub position_token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub metadata: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub master_edition: AccountInfo<'info>,
+
+    #[account(address = mpl_token_metadata::id())]
+    pub token_metadata_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_user_miner: u8, nonce_aury_vault: u8, _nonce_wallet_purchase: u8)]
+pub struct CancelMiner<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        mut,
+        constraint = miner_account.key() == user_miner_account.miner_type @ ErrorCode::InvalidAccounts
+    )]
+    pub miner_account: Box<Account<'info, MinerAccount>>,
+
+    #[account(
+        mut,
+        close = aury_to_authority,
+        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), user_miner_account.seed_key().as_ref() ],
+        bump = _nonce_user_miner,
+    )]
+    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+
+    // the buyer's purchases of the miner, may not exist for positions bought
+    // before wallet caps
+    #[account(
+        mut,
+        seeds = [ user_miner_account.miner_type.as_ref(), constants::WALLET_PURCHASE_PDA_SEED.as_ref(), user_miner_account.owner.as_ref() ],
+        bump = _nonce_wallet_purchase,
+    )]
+    pub wallet_purchase_account: AccountInfo<'info>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = nonce_aury_vault,
+    )]
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub aury_to: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = config_account.cancel_fee == 0 || treasury.key() == config_account.treasury @ ErrorCode::InvalidTreasuryAccount
+    )]
+    pub treasury: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub aury_to_authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_mine: u8, _nonce_mine_fees: u8)]
+pub struct CreateMine<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+        space = MineAccount::LEN,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [ mine_account.key().as_ref(), constants::MINE_FEES_PDA_SEED.as_ref() ],
+        bump = _nonce_mine_fees,
+        space = MineFeesAccount::LEN,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_mine: u8)]
+pub struct UpdateMineOwner<'info> {
+    #[account(
+        mut,
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_mine: u8)]
+pub struct UpdateMineCapacity<'info> {
+    #[account(
+        mut,
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_mine: u8)]
+pub struct UpdateMineAccess<'info> {
+    #[account(
+        mut,
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_mine: u8, _nonce_mine_member: u8, member: Pubkey)]
+pub struct AddMineMember<'info> {
+    #[account(
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [ mine_account.key().as_ref(), constants::MINE_MEMBER_PDA_SEED.as_ref(), member.as_ref() ],
+        bump = _nonce_mine_member,
+        space = MineMemberAccount::LEN,
+    )]
+    pub mine_member_account: Box<Account<'info, MineMemberAccount>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_mine: u8)]
+pub struct RemoveMineMember<'info> {
+    #[account(
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        mut,
+        close = owner,
+        constraint = mine_member_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts
+    )]
+    pub mine_member_account: Box<Account<'info, MineMemberAccount>>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_mine: u8)]
+pub struct UpdateMine<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
 
-        // update mine_account
-        mine_account.total_amount = mine_account
-            .total_amount
-            .checked_sub(what + user_miner_account.power)
-            .unwrap();
-        mine_account.x_total_amount -= x_aury;
+    #[account(
+        mut,
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
 
-        Ok(())
-    }
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_mine: u8)]
+pub struct UpdateMineFeeTiers<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        mut,
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8)]
+pub struct SyncMineFee<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        mut,
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_mine: u8)]
+pub struct UpdateMineFeeSplits<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
+        bump = _nonce_mine,
+    )]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        mut,
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce_aury_vault: u8)]
+pub struct DistributeMineFees<'info> {
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = nonce_aury_vault,
+    )]
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce_aury_vault: u8)]
+pub struct WithdrawMineFees<'info> {
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = nonce_aury_vault,
+    )]
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub aury_to: Box<Account<'info, TokenAccount>>,
+
+    pub owner: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
 #[instruction(_nonce_config: u8, _nonce_aury_vault: u8)]
-pub struct Initialize<'info> {
+pub struct RewardToMine<'info> {
     #[account(
-        init,
-        payer = initializer,
         seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
         bump = _nonce_config,
+        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
     )]
     pub config_account: Box<Account<'info, ConfigAccount>>,
 
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
     #[account(
         address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
     )]
     pub aury_mint: Box<Account<'info, Mint>>,
 
     #[account(
-        init,
-        payer = initializer,
-        token::mint = aury_mint,
-        token::authority = aury_vault,
+        mut,
         seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
         bump = _nonce_aury_vault,
     )]
     pub aury_vault: Box<Account<'info, TokenAccount>>,
 
     #[account(mut)]
-    pub initializer: Signer<'info>,
+    pub aury_from: Box<Account<'info, TokenAccount>>,
+
+    pub admin: Signer<'info>,
 
-    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce_config: u8)]
-pub struct UpdateConfig<'info> {
+#[instruction(_nonce_user_miner: u8, min_x_shares_out: u64)]
+pub struct AddMinersToMine<'info> {
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
     #[account(
         mut,
+        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), user_miner_account.seed_key().as_ref() ],
+        bump = _nonce_user_miner,
+    )]
+    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8, _nonce_user_miner: u8, nonce_aury_vault: u8)]
+pub struct ClaimMiner<'info> {
+    #[account(
         seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
         bump = _nonce_config,
     )]
     pub config_account: Box<Account<'info, ConfigAccount>>,
 
-    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    #[account(
+        mut,
+        close = aury_to_authority,
+        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), user_miner_account.seed_key().as_ref() ],
+        bump = _nonce_user_miner,
+    )]
+    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = nonce_aury_vault,
+    )]
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub aury_to: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = config_account.protocol_fee == 0 || treasury.key() == config_account.treasury @ ErrorCode::InvalidTreasuryAccount
+    )]
+    pub treasury: Box<Account<'info, TokenAccount>>,
+
+    pub aury_to_authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce_config: u8, _miner_created_at: u64, _nonce_miner: u8)]
-pub struct CreateMiner<'info> {
+#[instruction(_nonce_config: u8, _nonce_user_miner: u8)]
+pub struct UpdateAutoClaim<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), user_miner_account.seed_key().as_ref() ],
+        bump = _nonce_user_miner,
+    )]
+    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+
+    pub owner: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce_config: u8, _nonce_user_miner: u8, nonce_aury_vault: u8)]
+pub struct AutoClaimMiner<'info> {
+    #[account(
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = nonce_config,
+    )]
+    pub config_account: Box<Account<'info, ConfigAccount>>,
+
+    #[account(mut)]
+    pub mine_account: Box<Account<'info, MineAccount>>,
+
+    #[account(
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), user_miner_account.seed_key().as_ref() ],
+        bump = _nonce_user_miner,
+    )]
+    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
+
     #[account(
-        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
-        bump = _nonce_config,
-        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = nonce_aury_vault,
     )]
-    pub config_account: Box<Account<'info, ConfigAccount>>,
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    // must belong to the miner's holder
+    #[account(mut)]
+    pub aury_to: Box<Account<'info, TokenAccount>>,
 
     #[account(
-        init,
-        payer = admin,
-        seeds = [ _miner_created_at.to_string().as_ref(), constants::MINER_PDA_SEED.as_ref() ],
-        bump = _nonce_miner,
-        // 8: account's signature
-        // 4: name len
-        // 1 * 50: name max-len 50
-        // 8: cost
-        // 8: duration
-        // 8: limit
-        // 8: total_purchased
-        // 1: frozen_sales
-        space = 8 + (4 + 50) + 8 + 8 + 8 + 8 + 1,
+        mut,
+        constraint = config_account.protocol_fee == 0 || treasury.key() == config_account.treasury @ ErrorCode::InvalidTreasuryAccount
     )]
-    pub miner_account: Box<Account<'info, MinerAccount>>,
+    pub treasury: Box<Account<'info, TokenAccount>>,
 
-    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub keeper_to: Box<Account<'info, TokenAccount>>,
 
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
+    // the miner's holder, receives the rent of the closed user miner account
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+
+    pub keeper: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce_config: u8)]
-pub struct FreezeMiner<'info> {
+#[instruction(_nonce_config: u8, nonce_aury_vault: u8)]
+pub struct ClaimMany<'info> {
     #[account(
         seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
         bump = _nonce_config,
-        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
     )]
     pub config_account: Box<Account<'info, ConfigAccount>>,
 
     #[account(mut)]
-    pub miner_account: Box<Account<'info, MinerAccount>>,
+    pub mine_account: Box<Account<'info, MineAccount>>,
 
-    pub admin: Signer<'info>,
+    #[account(
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
 
-    pub system_program: Program<'info, System>,
-}
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
 
-#[derive(Accounts)]
-#[instruction(_nonce_config: u8)]
-pub struct RemoveMiner<'info> {
     #[account(
-        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
-        bump = _nonce_config,
-        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+        mut,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = nonce_aury_vault,
     )]
-    pub config_account: Box<Account<'info, ConfigAccount>>,
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub aury_to: Box<Account<'info, TokenAccount>>,
 
     #[account(
         mut,
-        close = admin,
+        constraint = config_account.protocol_fee == 0 || treasury.key() == config_account.treasury @ ErrorCode::InvalidTreasuryAccount
     )]
-    pub miner_account: Box<Account<'info, MinerAccount>>,
+    pub treasury: Box<Account<'info, TokenAccount>>,
 
-    pub admin: Signer<'info>,
+    #[account(mut)]
+    pub aury_to_authority: Signer<'info>,
+
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce_config: u8, _nonce_user_miner: u8, _nonce_aury_vault: u8)]
-pub struct PurchaseMiner<'info> {
+#[instruction(_nonce_config: u8, _nonce_user_miner: u8, nonce_aury_vault: u8)]
+pub struct Harvest<'info> {
     #[account(
         seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
         bump = _nonce_config,
-        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
     )]
     pub config_account: Box<Account<'info, ConfigAccount>>,
 
     #[account(mut)]
-    pub miner_account: Box<Account<'info, MinerAccount>>,
+    pub mine_account: Box<Account<'info, MineAccount>>,
 
     #[account(
-        init,
-        payer = aury_from_authority,
-        seeds = [ miner_account.key().as_ref(), constants::MINER_PDA_SEED.as_ref(), aury_from_authority.key().as_ref() ],
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
+    )]
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    #[account(
+        mut,
+        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), user_miner_account.seed_key().as_ref() ],
         bump = _nonce_user_miner,
     )]
     pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
@@ -536,194 +2827,440 @@ pub struct PurchaseMiner<'info> {
     #[account(
         mut,
         seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
-        bump = _nonce_aury_vault,
+        bump = nonce_aury_vault,
     )]
     pub aury_vault: Box<Account<'info, TokenAccount>>,
 
     #[account(mut)]
-    pub aury_from: Box<Account<'info, TokenAccount>>,
+    pub aury_to: Box<Account<'info, TokenAccount>>,
 
-    pub aury_from_authority: Signer<'info>,
+    #[account(
+        mut,
+        constraint = config_account.protocol_fee == 0 || treasury.key() == config_account.treasury @ ErrorCode::InvalidTreasuryAccount
+    )]
+    pub treasury: Box<Account<'info, TokenAccount>>,
+
+    pub aury_to_authority: Signer<'info>,
 
-    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce_config: u8, _nonce_mine: u8)]
-pub struct CreateMine<'info> {
+#[instruction(nonce_config: u8, nonce_user_miner: u8, nonce_new_user_miner: u8, nonce_aury_vault: u8, _nonce_wallet_purchase: u8)]
+pub struct RestakeMiner<'info> {
     #[account(
         seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
-        bump = _nonce_config,
+        bump = nonce_config,
         constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
     )]
     pub config_account: Box<Account<'info, ConfigAccount>>,
 
-    #[account(
-        init,
-        payer = owner,
-        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
-        bump = _nonce_mine,
-        // 8: account's signature
-        // 32: owner
-        // 4: name len
-        // 1 * 50: name max-len 50
-        // 8: fee
-        // 32: fee_to
-        // 8: total amount
-        // 8: x total amount
-        // 8: last_updated_at
-        // 4: shares vec len
-        // (8 + 8 + 8) * 400: shares limit is 400
-        // 8: timestamp
-        // 8: token amount
-        // 8: x token amount
-        space = 8 + 32 + (4 + 50) + 8 + 32 + 8 + 8 + 8 + (4 + (8 + 8 + 8) * constants::SHARES_LIMIT),
-    )]
+    #[account(mut)]
     pub mine_account: Box<Account<'info, MineAccount>>,
 
     #[account(
-        constraint = fee_to.mint == constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap() @ ErrorCode::InvalidFeeAccount
+        constraint = mine_fees_account.mine == mine_account.key() @ ErrorCode::InvalidAccounts,
     )]
-    pub fee_to: Box<Account<'info, TokenAccount>>,
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
 
-    pub owner: Signer<'info>,
+    // loaded and checked by the handler, which may close it
+    #[account(mut)]
+    pub user_miner_account: AccountInfo<'info>,
 
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
+    #[account(mut)]
+    pub new_miner_account: Box<Account<'info, MinerAccount>>,
 
-#[derive(Accounts)]
-#[instruction(_nonce_mine: u8)]
-pub struct UpdateMineOwner<'info> {
     #[account(
-        mut,
-        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
-        bump = _nonce_mine,
+        init_if_needed,
+        payer = owner,
+        seeds = [ new_miner_account.key().as_ref(), constants::WALLET_PURCHASE_PDA_SEED.as_ref(), owner.key().as_ref() ],
+        bump = _nonce_wallet_purchase,
+        space = WalletPurchaseAccount::LEN,
     )]
-    pub mine_account: Box<Account<'info, MineAccount>>,
+    pub wallet_purchase_account: Box<Account<'info, WalletPurchaseAccount>>,
 
-    pub owner: Signer<'info>,
-}
+    // the mine to restake into, may be mine_account
+    #[account(mut)]
+    pub new_mine_account: AccountInfo<'info>,
 
-#[derive(Accounts)]
-#[instruction(_nonce_config: u8, _nonce_mine: u8)]
-pub struct UpdateMine<'info> {
     #[account(
-        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
-        bump = _nonce_config,
-        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
+        constraint = new_mine_fees_account.mine == new_mine_account.key() @ ErrorCode::InvalidAccounts,
     )]
-    pub config_account: Box<Account<'info, ConfigAccount>>,
+    pub new_mine_fees_account: Box<Account<'info, MineFeesAccount>>,
+
+    // the new position, may be user_miner_account when restaking into the
+    // same miner type
+    #[account(mut)]
+    pub new_user_miner_account: AccountInfo<'info>,
+
+    #[account(
+        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
+    )]
+    pub aury_mint: Box<Account<'info, Mint>>,
 
     #[account(
         mut,
-        seeds = [ owner.key().as_ref(), constants::MINE_PDA_SEED.as_ref() ],
-        bump = _nonce_mine,
+        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
+        bump = nonce_aury_vault,
     )]
-    pub mine_account: Box<Account<'info, MineAccount>>,
+    pub aury_vault: Box<Account<'info, TokenAccount>>,
+
+    // receives what's left of the restaked aury after buying the new miner
+    #[account(mut)]
+    pub aury_to: Box<Account<'info, TokenAccount>>,
 
     #[account(
-        constraint = fee_to.mint == constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap() @ ErrorCode::InvalidFeeAccount
+        mut,
+        constraint = config_account.protocol_fee == 0 || treasury.key() == config_account.treasury @ ErrorCode::InvalidTreasuryAccount
     )]
-    pub fee_to: Box<Account<'info, TokenAccount>>,
+    pub treasury: Box<Account<'info, TokenAccount>>,
 
+    #[account(mut)]
     pub owner: Signer<'info>,
+
+    #[account(address = mpl_token_metadata::id())]
+    pub token_metadata_program: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce_config: u8, _nonce_aury_vault: u8)]
-pub struct RewardToMine<'info> {
+#[instruction(_nonce_config: u8)]
+pub struct MigrateConfig<'info> {
     #[account(
+        mut,
         seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
         bump = _nonce_config,
-        constraint = !config_account.freeze_program @ ErrorCode::ProgramFreezed
     )]
-    pub config_account: Box<Account<'info, ConfigAccount>>,
+    pub config_account: AccountInfo<'info>,
 
     #[account(mut)]
-    pub mine_account: Box<Account<'info, MineAccount>>,
+    pub admin: Signer<'info>,
 
-    #[account(
-        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-    )]
-    pub aury_mint: Box<Account<'info, Mint>>,
+    pub system_program: Program<'info, System>,
+}
 
+#[derive(Accounts)]
+#[instruction(_nonce_config: u8)]
+pub struct MigrateMiner<'info> {
     #[account(
-        mut,
-        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
-        bump = _nonce_aury_vault,
+        seeds = [ constants::CONFIG_PDA_SEED.as_ref() ],
+        bump = _nonce_config,
     )]
-    pub aury_vault: Box<Account<'info, TokenAccount>>,
+    pub config_account: Box<Account<'info, ConfigAccount>>,
 
     #[account(mut)]
-    pub aury_from: Box<Account<'info, TokenAccount>>,
+    pub miner_account: AccountInfo<'info>,
 
+    #[account(mut)]
     pub admin: Signer<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(_nonce_user_miner: u8)]
-pub struct AddMinersToMine<'info> {
+#[instruction(_nonce_mine_fees: u8)]
+pub struct MigrateMine<'info> {
     #[account(mut)]
-    pub mine_account: Box<Account<'info, MineAccount>>,
+    pub mine_account: AccountInfo<'info>,
 
     #[account(
-        mut,
-        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), owner.key().as_ref() ],
-        bump = _nonce_user_miner,
+        init_if_needed,
+        payer = payer,
+        seeds = [ mine_account.key().as_ref(), constants::MINE_FEES_PDA_SEED.as_ref() ],
+        bump = _nonce_mine_fees,
+        space = MineFeesAccount::LEN,
     )]
-    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+    pub mine_fees_account: Box<Account<'info, MineFeesAccount>>,
 
-    pub owner: Signer<'info>,
+    // pays the rent of the grown account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateUserMiner<'info> {
+    #[account(mut)]
+    pub user_miner_account: AccountInfo<'info>,
+
+    // pays the rent of the grown account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[account]
+#[derive(Default)]
+pub struct ConfigAccount {
+    pub version: u8,
+    pub admin_key: Pubkey,
+    pub freeze_program: bool,
+    pub min_mine_fee: u64,
+    pub max_mine_fee: u64,
+    pub mine_update_delay: u64,
+    pub protocol_fee: u64,
+    pub treasury: Pubkey,
+    pub keeper_tip: u64,
+    pub cancel_fee: u64,
+    pub reserved: [u64; 16],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct AuryShare {
+    pub timestamp: u64,
+    pub token_amount: u64,
+    pub x_token_amount: u64,
+}
+
+/// A fee applied to positions of `miner_type` (any type when default) whose
+/// power is at least `min_power`. The first matching tier wins.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct FeeTier {
+    pub miner_type: Pubkey,
+    pub min_power: u64,
+    pub fee: u64,
+}
+
+/// A recipient token account of a mine's fees and its share of them, in bps.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
+pub struct FeeSplit {
+    pub recipient: Pubkey,
+    pub share: u64,
+}
+
+/// A config update waiting out its notice period in a `PendingConfigChange`.
+/// Every update but freezing the program goes through one.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone)]
+pub enum ConfigChange {
+    Mine {
+        min_mine_fee: u64,
+        max_mine_fee: u64,
+        mine_update_delay: u64,
+    },
+    Admin {
+        new_admin: Pubkey,
+    },
+    ProtocolFee {
+        protocol_fee: u64,
+        treasury: Pubkey,
+    },
+    KeeperTip {
+        keeper_tip: u64,
+    },
+    CancelFee {
+        cancel_fee: u64,
+    },
+}
+
+impl ConfigChange {
+    /// Seeds the `PendingConfigChange` of this kind of change.
+    pub fn kind(&self) -> u8 {
+        match self {
+            ConfigChange::Mine { .. } => 0,
+            ConfigChange::Admin { .. } => 1,
+            ConfigChange::ProtocolFee { .. } => 2,
+            ConfigChange::KeeperTip { .. } => 3,
+            ConfigChange::CancelFee { .. } => 4,
+        }
+    }
+}
+
+impl Default for ConfigChange {
+    fn default() -> Self {
+        ConfigChange::Admin {
+            new_admin: Pubkey::default(),
+        }
+    }
+}
+
+/// Who may add miners to a mine: anyone, wallets holding a
+/// `MineMemberAccount` for it, or holders of an NFT from a verified collection.
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq)]
+pub enum MineAccess {
+    Public,
+    AllowList,
+    Collection,
+}
+
+impl Default for MineAccess {
+    fn default() -> Self {
+        MineAccess::Public
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct MineAccount {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub name: String,
+    pub fee: u64,
+    pub total_amount: u64,
+    pub x_total_amount: u64,
+    pub last_updated_at: u64,
+    pub shares: Vec<AuryShare>,
+    pub uri: String,
+    pub max_total_amount: u64,
+    pub max_miners: u64,
+    pub min_power: u64,
+    pub active_miners: u64,
+    pub access: MineAccess,
+    pub access_collection: Pubkey,
+    pub pending_rewards: u64,
+    pub accrued_fees: u64,
+    pub reserved: [u64; 16],
+}
+
+#[account]
+#[derive(Default)]
+pub struct MineMemberAccount {
+    pub version: u8,
+    pub mine: Pubkey,
+    pub member: Pubkey,
+    pub reserved: [u64; 4],
+}
+
+/// The fee tiers and splits of a mine, kept apart so the mine's share history
+/// has room to grow.
+#[account]
+#[derive(Default)]
+pub struct MineFeesAccount {
+    pub version: u8,
+    pub mine: Pubkey,
+    pub fee_tiers: Vec<FeeTier>,
+    pub fee_splits: Vec<FeeSplit>,
+    pub reserved: [u64; 4],
+}
+
+/// How a miner's unit price moves: flat at `cost`, up by `price_step` per unit
+/// sold (linear), up by `price_step` bps per unit sold (exponential), or down
+/// by `price_step` per second since `sale_start` until `price_floor` (dutch
+/// auction).
+#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, PartialEq)]
+pub enum PricingCurve {
+    Flat,
+    Linear,
+    Exponential,
+    DutchAuction,
+}
+
+impl Default for PricingCurve {
+    fn default() -> Self {
+        PricingCurve::Flat
+    }
+}
+
+#[account]
+#[derive(Default)]
+pub struct MinerAccount {
+    pub version: u8,
+    pub name: String,
+    pub cost: u64,
+    pub duration: u64,
+    pub limit: u64,
+    pub total_purchased: u64,
+    pub frozen_sales: bool,
+    pub uri: String,
+    pub required_collection: Pubkey,
+    pub max_per_wallet: u64,
+    pub sale_start: u64,
+    pub sale_end: u64,
+    pub pricing: PricingCurve,
+    pub price_step: u64,
+    pub price_floor: u64,
+    pub power_multiplier: u64,
+    pub reserved: [u64; 16],
 }
 
-#[derive(Accounts)]
-#[instruction(_nonce_user_miner: u8, nonce_aury_vault: u8)]
-pub struct ClaimMiner<'info> {
-    #[account(mut)]
-    pub mine_account: Box<Account<'info, MineAccount>>,
+#[account]
+#[derive(Default)]
+pub struct WalletPurchaseAccount {
+    pub version: u8,
+    pub miner: Pubkey,
+    pub wallet: Pubkey,
+    pub purchased: u64,
+    pub reserved: [u64; 4],
+}
 
-    #[account(
-        mut,
-        close = aury_to_authority,
-        seeds = [ user_miner_account.miner_type.as_ref(), constants::MINER_PDA_SEED.as_ref(), aury_to_authority.key().as_ref() ],
-        bump = _nonce_user_miner,
-    )]
-    pub user_miner_account: Box<Account<'info, UserMinerAccount>>,
+#[account]
+#[derive(Default)]
+pub struct PendingConfigChange {
+    pub version: u8,
+    pub change: ConfigChange,
+    pub queued_at: u64,
+    pub execute_at: u64,
+    pub reserved: [u64; 4],
+}
 
-    #[account(
-        address = constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-    )]
-    pub aury_mint: Box<Account<'info, Mint>>,
+#[account]
+#[derive(Default)]
+pub struct UserMinerAccount {
+    pub version: u8,
+    pub owner: Pubkey,
+    pub miner_type: Pubkey,
+    pub power: u64,
+    pub duration: u64,
+    pub mining_start_at: u64,
+    pub mine_key: Pubkey,
+    pub x_aury_amount: u64,
+    pub locked_fee: u64,
+    pub power_multiplier: u64,
+    pub boosted_power: u64,
+    pub position_mint: Pubkey,
+    pub last_harvested_at: u64,
+    pub auto_claim: bool,
+    pub amount: u64,
+    pub fee_locked: bool,
+    pub reserved: [u64; 16],
+}
 
-    #[account(
-        mut,
-        seeds = [ constants::AURY_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap().as_ref() ],
-        bump = nonce_aury_vault,
-    )]
-    pub aury_vault: Box<Account<'info, TokenAccount>>,
+#[event]
+pub struct MineFeeUpdated {
+    pub mine: Pubkey,
+    pub fee: u64,
+    pub next_update_at: u64,
+}
 
-    #[account(mut)]
-    pub aury_to: Box<Account<'info, TokenAccount>>,
+#[event]
+pub struct MineFeeSplitsUpdated {
+    pub mine: Pubkey,
+    pub fee_splits: Vec<FeeSplit>,
+    pub next_update_at: u64,
+}
 
-    #[account(
-        mut,
-        constraint = fee_to.key() == mine_account.fee_to @ ErrorCode::InvalidFeeAccount
-    )]
-    pub fee_to: Box<Account<'info, TokenAccount>>,
+#[event]
+pub struct ConfigChangeQueued {
+    pub pending_config_change: Pubkey,
+    pub change: ConfigChange,
+    pub execute_at: u64,
+}
 
-    pub aury_to_authority: Signer<'info>,
+#[event]
+pub struct ConfigChangeCancelled {
+    pub pending_config_change: Pubkey,
+    pub change: ConfigChange,
+}
 
-    pub token_program: Program<'info, Token>,
+#[event]
+pub struct MinersAddedToMine {
+    pub mine: Pubkey,
+    pub owner: Pubkey,
+    pub miners: u64,
+    pub power: u64,
+    pub mining_power: u64,
+    pub x_shares: u64,
 }
 
-#[account]
-#[derive(Default)]
-pub struct ConfigAccount {
+/// Layouts written before accounts carried a version byte. They are only
+/// read by the `migrate_*` instructions.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyConfigAccount {
     pub admin_key: Pubkey,
     pub freeze_program: bool,
     pub min_mine_fee: u64,
@@ -731,16 +3268,8 @@ pub struct ConfigAccount {
     pub mine_update_delay: u64,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Copy, Clone, Default)]
-pub struct AuryShare {
-    pub timestamp: u64,
-    pub token_amount: u64,
-    pub x_token_amount: u64,
-}
-
-#[account]
-#[derive(Default)]
-pub struct MineAccount {
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyMineAccount {
     pub owner: Pubkey,
     pub name: String,
     pub fee: u64,
@@ -751,9 +3280,8 @@ pub struct MineAccount {
     pub shares: Vec<AuryShare>,
 }
 
-#[account]
-#[derive(Default)]
-pub struct MinerAccount {
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyMinerAccount {
     pub name: String,
     pub cost: u64,
     pub duration: u64,
@@ -762,9 +3290,8 @@ pub struct MinerAccount {
     pub frozen_sales: bool,
 }
 
-#[account]
-#[derive(Default)]
-pub struct UserMinerAccount {
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LegacyUserMinerAccount {
     pub owner: Pubkey,
     pub miner_type: Pubkey,
     pub power: u64,
@@ -775,6 +3302,47 @@ pub struct UserMinerAccount {
 }
 
 impl ConfigAccount {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 32: admin_key
+    // 1: freeze_program
+    // 8: min_mine_fee
+    // 8: max_mine_fee
+    // 8: mine_update_delay
+    // 8: protocol_fee
+    // 32: treasury
+    // 8: keeper_tip
+    // 8: cancel_fee
+    // 8 * 16: reserved
+    pub const LEN: usize = 8 + 1 + 32 + 1 + 8 + 8 + 8 + 8 + 32 + 8 + 8 + 8 * 16;
+    pub const LEGACY_LEN: usize = 8 + 32 + 1 + 8 + 8 + 8;
+
+    pub fn upgrade(data: &[u8], original_len: usize) -> std::result::Result<Self, ProgramError> {
+        let mut account = if original_len == Self::LEGACY_LEN {
+            let legacy = LegacyConfigAccount::deserialize(&mut &data[8..])
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+            ConfigAccount {
+                admin_key: legacy.admin_key,
+                freeze_program: legacy.freeze_program,
+                min_mine_fee: legacy.min_mine_fee,
+                max_mine_fee: legacy.max_mine_fee,
+                mine_update_delay: legacy.mine_update_delay,
+                ..Default::default()
+            }
+        } else {
+            match data[8] {
+                Self::VERSION => ConfigAccount::try_deserialize(&mut &data[..])?,
+                _ => return Err(ErrorCode::UnknownAccountVersion.into()),
+            }
+        };
+        account.version = Self::VERSION;
+        account.reserved = Default::default();
+
+        Ok(account)
+    }
+
     pub fn assert_admin(&self, signer: &Signer) -> ProgramResult {
         if self.admin_key != *signer.key {
             return Err(ErrorCode::NotAdmin.into());
@@ -790,9 +3358,89 @@ impl ConfigAccount {
 
         Ok(())
     }
+
+    /// Brings a fee set under older bounds back within the current ones.
+    pub fn clamp_mine_fee(&self, fee: u64) -> u64 {
+        fee.max(self.min_mine_fee).min(self.max_mine_fee)
+    }
 }
 
 impl MineAccount {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 32: owner
+    // 4: name len
+    // 1 * 50: name max-len 50
+    // 8: fee
+    // 8: total amount
+    // 8: x total amount
+    // 8: last_updated_at
+    // 4: shares vec len
+    // (8 + 8 + 8) * 400: shares limit is 400
+    // 8: timestamp
+    // 8: token amount
+    // 8: x token amount
+    // 4: uri len
+    // 1 * 200: uri max-len 200
+    // 8: max_total_amount
+    // 8: max_miners
+    // 8: min_power
+    // 8: active_miners
+    // 1: access
+    // 32: access_collection
+    // 8: pending_rewards
+    // 8: accrued_fees
+    // 8 * 16: reserved
+    pub const LEN: usize = 8
+        + 1
+        + 32
+        + (4 + constants::NAME_MAX_LEN)
+        + 8
+        + 8
+        + 8
+        + 8
+        + (4 + (8 + 8 + 8) * constants::SHARES_LIMIT)
+        + (4 + constants::URI_MAX_LEN)
+        + 8
+        + 8
+        + 8
+        + 8
+        + 1
+        + 32
+        + 8
+        + 8
+        + 8 * 16;
+    pub const LEGACY_LEN: usize =
+        8 + 32 + (4 + 50) + 8 + 32 + 8 + 8 + 8 + (4 + (8 + 8 + 8) * constants::SHARES_LIMIT);
+
+    pub fn upgrade(data: &[u8], original_len: usize) -> std::result::Result<Self, ProgramError> {
+        let mut account = if original_len == Self::LEGACY_LEN {
+            let legacy = LegacyMineAccount::deserialize(&mut &data[8..])
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+            MineAccount {
+                owner: legacy.owner,
+                name: legacy.name,
+                fee: legacy.fee,
+                total_amount: legacy.total_amount,
+                x_total_amount: legacy.x_total_amount,
+                last_updated_at: legacy.last_updated_at,
+                shares: legacy.shares,
+                ..Default::default()
+            }
+        } else {
+            match data[8] {
+                Self::VERSION => MineAccount::try_deserialize(&mut &data[..])?,
+                _ => return Err(ErrorCode::UnknownAccountVersion.into()),
+            }
+        };
+        account.version = Self::VERSION;
+        account.reserved = Default::default();
+
+        Ok(account)
+    }
+
     pub fn assert_owner(&self, signer: &Signer) -> ProgramResult {
         if self.owner != *signer.key {
             return Err(ErrorCode::NotMineOwner.into());
@@ -801,6 +3449,188 @@ impl MineAccount {
         Ok(())
     }
 
+    pub fn fee_for(
+        &self,
+        mine_fees_account: &MineFeesAccount,
+        miner_type: Pubkey,
+        power: u64,
+    ) -> u64 {
+        mine_fees_account
+            .fee_tiers
+            .iter()
+            .find(|tier| {
+                (tier.miner_type == Pubkey::default() || tier.miner_type == miner_type)
+                    && power >= tier.min_power
+            })
+            .map_or(self.fee, |tier| tier.fee)
+    }
+
+    /// Checks that `member` may join the mine, reading the proof accounts its
+    /// access mode needs from the front of `proofs`. Returns how many were used.
+    pub fn assert_member(
+        &self,
+        mine_key: Pubkey,
+        member: &Pubkey,
+        proofs: &[AccountInfo],
+    ) -> std::result::Result<usize, ProgramError> {
+        match self.access {
+            MineAccess::Public => Ok(0),
+            MineAccess::AllowList => {
+                let mine_member_info = proofs.first().ok_or(ErrorCode::NotMineMember)?;
+                let mine_member_account = Account::<MineMemberAccount>::try_from(mine_member_info)?;
+                if !(mine_member_account.mine == mine_key && mine_member_account.member == *member)
+                {
+                    return Err(ErrorCode::NotMineMember.into());
+                }
+
+                Ok(1)
+            }
+            MineAccess::Collection => {
+                if proofs.len() < 2 {
+                    return Err(ErrorCode::NotCollectionHolder.into());
+                }
+                let token_account = Account::<TokenAccount>::try_from(&proofs[0])?;
+                assert_collection_holder(
+                    member,
+                    &token_account,
+                    &proofs[1],
+                    &self.access_collection,
+                )?;
+
+                Ok(2)
+            }
+        }
+    }
+
+    /// Boosts the miner's power, mints x-shares for it and locks in the fee it
+    /// joins with.
+    pub fn join(
+        &mut self,
+        mine_key: Pubkey,
+        mine_fees_account: &MineFeesAccount,
+        user_miner_account: &mut UserMinerAccount,
+        now: u64,
+    ) -> ProgramResult {
+        if user_miner_account.mining_start_at > 0 {
+            return Err(ErrorCode::MinerAlreadyMining.into());
+        }
+
+        // boost the power the miner mines with, its principal stays the same
+        user_miner_account.boosted_power = user_miner_account.boost();
+        let mining_power = user_miner_account.mining_power();
+
+        self.assert_joinable(user_miner_account.power, mining_power)?;
+
+        let what = x_shares_for(mining_power, self.total_amount, self.x_total_amount, false);
+        if what == 0 {
+            return Err(ErrorCode::XSharesBelowMin.into());
+        }
+
+        self.x_total_amount += what;
+        user_miner_account.x_aury_amount += what;
+        self.total_amount += mining_power;
+        self.active_miners += 1;
+        user_miner_account.mine_key = mine_key;
+        user_miner_account.mining_start_at = now;
+
+        // lock in the fee the miner joins with
+        user_miner_account.locked_fee = self.fee_for(
+            mine_fees_account,
+            user_miner_account.miner_type,
+            user_miner_account.power,
+        );
+        user_miner_account.fee_locked = true;
+
+        Ok(())
+    }
+
+    /// Reward of the miner before fees: the value of its x-shares above its
+    /// mining power, at the last share snapshot within its mining period.
+    pub fn reward_for(&self, user_miner_account: &UserMinerAccount) -> u64 {
+        let mining_power = user_miner_account.mining_power();
+        let mining_end_timestamp = user_miner_account.mining_start_at + user_miner_account.duration;
+
+        for share in self.shares.iter().rev() {
+            if share.timestamp <= mining_end_timestamp {
+                let what = x_shares_value(
+                    user_miner_account.x_aury_amount,
+                    share.token_amount,
+                    share.x_token_amount,
+                );
+
+                return what.saturating_sub(mining_power);
+            }
+        }
+
+        0
+    }
+
+    /// Gains of the miner since it joined or last harvested, before fees, and
+    /// the x-shares they are worth: the value of its x-shares above its mining
+    /// power at the latest share snapshot, up to the end of its mining period.
+    pub fn harvestable(&self, user_miner_account: &UserMinerAccount, now: u64) -> (u64, u64) {
+        let mining_end_timestamp = user_miner_account.mining_start_at + user_miner_account.duration;
+        let since = user_miner_account
+            .mining_start_at
+            .max(user_miner_account.last_harvested_at);
+
+        let share = self
+            .shares
+            .iter()
+            .rev()
+            .find(|share| share.timestamp <= now.min(mining_end_timestamp));
+        let share = match share {
+            Some(share) if share.timestamp > since => share,
+            _ => return (0, 0),
+        };
+
+        let value = x_shares_value(
+            user_miner_account.x_aury_amount,
+            share.token_amount,
+            share.x_token_amount,
+        );
+        let gains = value.saturating_sub(user_miner_account.mining_power());
+
+        // round the burnt x-shares up so the miner never takes out more than
+        // its gains
+        let x_gains = x_shares_for(gains, share.token_amount, share.x_token_amount, true);
+
+        (gains, x_gains.min(user_miner_account.x_aury_amount))
+    }
+
+    /// Burns the miner's x-shares and takes its mining power and `reward` out
+    /// of the mine.
+    pub fn leave(&mut self, user_miner_account: &UserMinerAccount, reward: u64) {
+        self.total_amount = self
+            .total_amount
+            .checked_sub(reward + user_miner_account.mining_power())
+            .unwrap();
+        self.x_total_amount -= user_miner_account.x_aury_amount;
+        self.active_miners = self.active_miners.saturating_sub(1);
+
+        // whatever the last miner leaves behind waits for the next rewards
+        if self.x_total_amount == 0 {
+            self.pending_rewards += self.total_amount;
+            self.total_amount = 0;
+        }
+    }
+
+    pub fn assert_joinable(&self, power: u64, mining_power: u64) -> ProgramResult {
+        if power < self.min_power {
+            return Err(ErrorCode::MinerPowerTooLow.into());
+        }
+
+        if self.max_total_amount > 0 && self.total_amount + mining_power > self.max_total_amount {
+            return Err(ErrorCode::MineCapacityReached.into());
+        }
+
+        if self.max_miners > 0 && self.active_miners + 1 > self.max_miners {
+            return Err(ErrorCode::MineMinersLimit.into());
+        }
+
+        Ok(())
+    }
+
     pub fn assert_updatable(&self, mine_update_delay: u64) -> ProgramResult {
         let now = Clock::get().unwrap().unix_timestamp as u64;
 
@@ -812,21 +3642,411 @@ impl MineAccount {
     }
 }
 
+// accounts created through a cpi, as program addresses are, can't be larger
+// than MAX_PERMITTED_DATA_INCREASE
+const _: [(); 0] = [(); (MineAccount::LEN > MAX_PERMITTED_DATA_INCREASE) as usize];
+
+impl MineMemberAccount {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 32: mine
+    // 32: member
+    // 8 * 4: reserved
+    pub const LEN: usize = 8 + 1 + 32 + 32 + 8 * 4;
+}
+
+impl MineFeesAccount {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 32: mine
+    // 4: fee_tiers vec len
+    // (32 + 8 + 8) * 8: fee tiers limit is 8
+    // 4: fee_splits vec len
+    // (32 + 8) * 8: fee splits limit is 8
+    // 8 * 4: reserved
+    pub const LEN: usize = 8
+        + 1
+        + 32
+        + (4 + (32 + 8 + 8) * constants::FEE_TIERS_LIMIT)
+        + (4 + (32 + 8) * constants::FEE_SPLITS_LIMIT)
+        + 8 * 4;
+}
+
+impl WalletPurchaseAccount {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 32: miner
+    // 32: wallet
+    // 8: purchased
+    // 8 * 4: reserved
+    pub const LEN: usize = 8 + 1 + 32 + 32 + 8 + 8 * 4;
+
+    /// Counts `amount` more miners bought by `wallet`, towards the miner's
+    /// `max_per_wallet`.
+    pub fn record(&mut self, miner: Pubkey, wallet: Pubkey, amount: u64) {
+        self.version = Self::VERSION;
+        self.miner = miner;
+        self.wallet = wallet;
+        self.purchased += amount;
+    }
+
+    /// Gives `amount` refunded miners back to the wallet's cap.
+    pub fn release(&mut self, amount: u64) {
+        self.purchased = self.purchased.saturating_sub(amount);
+    }
+}
+
+impl PendingConfigChange {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 1 + 40: change, the protocol fee variant is the largest
+    // 8: queued_at
+    // 8: execute_at
+    // 8 * 4: reserved
+    pub const LEN: usize = 8 + 1 + (1 + 40) + 8 + 8 + 8 * 4;
+
+    pub fn assert_executable(&self, now: u64) -> ProgramResult {
+        if now < self.execute_at {
+            return Err(ErrorCode::ConfigChangeNotReady.into());
+        }
+
+        Ok(())
+    }
+}
+
 impl MinerAccount {
-    pub fn assert_purchasable(&self, amount: u64) -> ProgramResult {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 4: name len
+    // 1 * 50: name max-len 50
+    // 8: cost
+    // 8: duration
+    // 8: limit
+    // 8: total_purchased
+    // 1: frozen_sales
+    // 4: uri len
+    // 1 * 200: uri max-len 200
+    // 32: required_collection
+    // 8: max_per_wallet
+    // 8: sale_start
+    // 8: sale_end
+    // 1: pricing
+    // 8: price_step
+    // 8: price_floor
+    // 8: power_multiplier
+    // 8 * 16: reserved
+    pub const LEN: usize = 8
+        + 1
+        + (4 + constants::NAME_MAX_LEN)
+        + 8
+        + 8
+        + 8
+        + 8
+        + 1
+        + (4 + constants::URI_MAX_LEN)
+        + 32
+        + 8
+        + 8
+        + 8
+        + 1
+        + 8
+        + 8
+        + 8
+        + 8 * 16;
+    pub const LEGACY_LEN: usize = 8 + (4 + 50) + 8 + 8 + 8 + 8 + 1;
+
+    pub fn upgrade(data: &[u8], original_len: usize) -> std::result::Result<Self, ProgramError> {
+        let mut account = if original_len == Self::LEGACY_LEN {
+            let legacy = LegacyMinerAccount::deserialize(&mut &data[8..])
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+            MinerAccount {
+                name: legacy.name,
+                cost: legacy.cost,
+                duration: legacy.duration,
+                limit: legacy.limit,
+                total_purchased: legacy.total_purchased,
+                frozen_sales: legacy.frozen_sales,
+                ..Default::default()
+            }
+        } else {
+            match data[8] {
+                Self::VERSION => MinerAccount::try_deserialize(&mut &data[..])?,
+                _ => return Err(ErrorCode::UnknownAccountVersion.into()),
+            }
+        };
+        account.version = Self::VERSION;
+        account.reserved = Default::default();
+
+        Ok(account)
+    }
+
+    pub fn assert_purchasable(&self, amount: u64, wallet_purchased: u64) -> ProgramResult {
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+
         if self.frozen_sales {
             return Err(ErrorCode::MinerFrozenSells.into());
         }
 
+        if self.sale_start > 0 && now < self.sale_start {
+            return Err(ErrorCode::MinerSaleNotStarted.into());
+        }
+
+        if self.sale_end > 0 && now >= self.sale_end {
+            return Err(ErrorCode::MinerSaleEnded.into());
+        }
+
         if self.limit > 0 && self.total_purchased + amount > self.limit {
             return Err(ErrorCode::MinerPurchaseLimit.into());
         }
 
+        if self.max_per_wallet > 0 && wallet_purchased + amount > self.max_per_wallet {
+            return Err(ErrorCode::MinerWalletLimit.into());
+        }
+
         Ok(())
     }
+
+    /// Total price of the next `amount` units under the miner's pricing curve.
+    pub fn price_for(&self, amount: u64) -> std::result::Result<u64, ProgramError> {
+        let now = Clock::get().unwrap().unix_timestamp as u64;
+        let cost = self.cost as u128;
+        let step = self.price_step as u128;
+        let sold = self.total_purchased as u128;
+        let units = amount as u128;
+
+        let total = match self.pricing {
+            PricingCurve::Flat => cost.checked_mul(units),
+            PricingCurve::Linear => {
+                // sum of cost + step * n for n in sold..sold + amount
+                let steps = units
+                    .checked_mul(sold)
+                    .and_then(|s| s.checked_add(units * units.saturating_sub(1) / 2));
+                steps
+                    .and_then(|s| s.checked_mul(step))
+                    .and_then(|s| s.checked_add(cost.checked_mul(units)?))
+            }
+            PricingCurve::Exponential if step == 0 => cost.checked_mul(units),
+            PricingCurve::Exponential => {
+                // geometric series from the price of the next unit
+                let first = pow_bps(self.price_step, self.total_purchased)
+                    .and_then(|g| g.checked_mul(cost))
+                    .map(|p| p / PRICE_SCALE);
+                let growth = pow_bps(self.price_step, amount);
+                first
+                    .zip(growth)
+                    .and_then(|(first, growth)| first.checked_mul(growth - PRICE_SCALE))
+                    .and_then(|s| s.checked_mul(BPS_MULTIPLIER as u128))
+                    .map(|s| s / (PRICE_SCALE * step))
+            }
+            PricingCurve::DutchAuction => {
+                let elapsed = now.saturating_sub(self.sale_start) as u128;
+                let unit_price = cost
+                    .saturating_sub(step.saturating_mul(elapsed))
+                    .max(self.price_floor as u128);
+                unit_price.checked_mul(units)
+            }
+        };
+
+        total
+            .and_then(|total| total.try_into().ok())
+            .ok_or_else(|| ErrorCode::MathOverflow.into())
+    }
+
+    /// Most units `budget` buys under the miner's pricing curve, none if the
+    /// miner is free.
+    pub fn amount_for(&self, budget: u64) -> std::result::Result<u64, ProgramError> {
+        let unit_price = self.price_for(1)?;
+        if unit_price == 0 {
+            return Ok(0);
+        }
+
+        // no unit is cheaper than the one before it, so the budget buys at
+        // most what it would at the next unit's price
+        let (mut low, mut high) = (0, budget / unit_price);
+        while low < high {
+            let mid = high - (high - low) / 2;
+            match self.price_for(mid) {
+                Ok(price) if price <= budget => low = mid,
+                _ => high = mid - 1,
+            }
+        }
+
+        Ok(low)
+    }
+
+    /// Miners restricted to a collection can only be bought by holders of one
+    /// of its NFTs, proven by a token account and its metadata in `proofs`.
+    pub fn assert_buyer(&self, buyer: &Pubkey, proofs: &[AccountInfo]) -> ProgramResult {
+        if self.required_collection == Pubkey::default() {
+            return Ok(());
+        }
+
+        if proofs.len() < 2 {
+            return Err(ErrorCode::NotCollectionHolder.into());
+        }
+        let token_account = Account::<TokenAccount>::try_from(&proofs[0])?;
+
+        assert_collection_holder(buyer, &token_account, &proofs[1], &self.required_collection)
+    }
 }
 
 impl UserMinerAccount {
+    pub const VERSION: u8 = 1;
+
+    // 8: account's signature
+    // 1: version
+    // 32: owner
+    // 32: miner_type
+    // 8: power
+    // 8: duration
+    // 8: mining_start_at
+    // 32: mine_key
+    // 8: x_aury_amount
+    // 8: locked_fee
+    // 8: power_multiplier
+    // 8: boosted_power
+    // 32: position_mint
+    // 8: last_harvested_at
+    // 1: auto_claim
+    // 8: amount
+    // 1: fee_locked
+    // 8 * 16: reserved
+    pub const LEN: usize =
+        8 + 1 + 32 + 32 + 8 + 8 + 8 + 32 + 8 + 8 + 8 + 8 + 32 + 8 + 1 + 8 + 1 + 8 * 16;
+    pub const LEGACY_LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 32 + 8;
+
+    pub fn upgrade(data: &[u8], original_len: usize) -> std::result::Result<Self, ProgramError> {
+        let mut account = if original_len == Self::LEGACY_LEN {
+            let legacy = LegacyUserMinerAccount::deserialize(&mut &data[8..])
+                .map_err(|_| ProgramError::InvalidAccountData)?;
+            UserMinerAccount {
+                owner: legacy.owner,
+                miner_type: legacy.miner_type,
+                power: legacy.power,
+                duration: legacy.duration,
+                mining_start_at: legacy.mining_start_at,
+                mine_key: legacy.mine_key,
+                x_aury_amount: legacy.x_aury_amount,
+                ..Default::default()
+            }
+        } else {
+            match data[8] {
+                Self::VERSION => UserMinerAccount::try_deserialize(&mut &data[..])?,
+                _ => return Err(ErrorCode::UnknownAccountVersion.into()),
+            }
+        };
+        account.version = Self::VERSION;
+        account.reserved = Default::default();
+
+        Ok(account)
+    }
+
+    /// Key the position's address is derived from. Positions carry the nft
+    /// they were minted with, so they stay put when it changes hands, while
+    /// positions bought before nfts are keyed on their buyer.
+    pub fn seed_key(&self) -> Pubkey {
+        if self.position_mint == Pubkey::default() {
+            return self.owner;
+        }
+
+        self.position_mint
+    }
+
+    /// Miners minted as an nft belong to whoever holds it, proven by their
+    /// token account in `proofs`. Others belong to the wallet that bought them.
+    pub fn holder(&self, proofs: &[AccountInfo]) -> std::result::Result<Pubkey, ProgramError> {
+        if self.position_mint == Pubkey::default() {
+            return Ok(self.owner);
+        }
+
+        let token_account_info = proofs.first().ok_or(ErrorCode::NotMinerOwner)?;
+        let token_account = Account::<TokenAccount>::try_from(token_account_info)?;
+        if !(token_account.mint == self.position_mint && token_account.amount == 1) {
+            return Err(ErrorCode::NotMinerOwner.into());
+        }
+
+        Ok(token_account.owner)
+    }
+
+    pub fn assert_holder(&self, signer: &Pubkey, proofs: &[AccountInfo]) -> ProgramResult {
+        if self.holder(proofs)? != *signer {
+            return Err(ErrorCode::NotMinerOwner.into());
+        }
+
+        Ok(())
+    }
+
+    /// Number of miners bought with the position. Positions bought before it
+    /// was recorded were all bought at the miner's flat cost.
+    pub fn purchased_amount(&self, miner_account: &MinerAccount) -> u64 {
+        if self.amount > 0 || miner_account.cost == 0 {
+            return self.amount;
+        }
+
+        self.power / miner_account.cost
+    }
+
+    /// Number of proof accounts `assert_holder` reads.
+    pub fn holder_proofs(&self) -> usize {
+        if self.position_mint == Pubkey::default() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Power boosted by the miner type's multiplier. Miners bought before
+    /// multipliers existed mine at their plain power.
+    pub fn boost(&self) -> u64 {
+        if self.power_multiplier == 0 {
+            return self.power;
+        }
+
+        (self.power as u128)
+            .checked_mul(self.power_multiplier as u128)
+            .unwrap()
+            .checked_div(BPS_MULTIPLIER as u128)
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    /// Power the miner holds x-shares for in its mine. Rewards are the value
+    /// of those shares above it, while `power` is the principal paid back.
+    pub fn mining_power(&self) -> u64 {
+        if self.boosted_power == 0 {
+            return self.power;
+        }
+
+        self.boosted_power
+    }
+
+    /// The lower of the fee locked in when joining the mine and the mine's
+    /// current fee. Positions that joined before fees were locked in pay the
+    /// current fee.
+    pub fn effective_fee(
+        &self,
+        mine_account: &MineAccount,
+        mine_fees_account: &MineFeesAccount,
+    ) -> u64 {
+        let fee = mine_account.fee_for(mine_fees_account, self.miner_type, self.power);
+        if !self.fee_locked {
+            return fee;
+        }
+
+        fee.min(self.locked_fee)
+    }
+
     pub fn assert_claimable(&self, mine_key: Pubkey) -> ProgramResult {
         if !(self.mining_start_at > 0) {
             return Err(ErrorCode::ClaimUnavailable.into());
@@ -847,24 +4067,244 @@ pub enum ErrorCode {
     ProgramFreezed, // 6001, 0x1771
     #[msg("Miner purchase limit")]
     MinerPurchaseLimit, // 6002, 0x1772
-    #[msg("Token transfer failed")]
-    TokenTransferFailed, // 6003, 0x1773
     #[msg("Invalid mine fee")]
-    InvalidMineFee, // 6004, 0x1774
+    InvalidMineFee, // 6003, 0x1773
     #[msg("Not mine owner")]
-    NotMineOwner, // 6005, 0x1775
+    NotMineOwner, // 6004, 0x1774
     #[msg("Not miner owner")]
-    NotMinerOwner, // 6006, 0x1776
+    NotMinerOwner, // 6005, 0x1775
     #[msg("Non available miners")]
-    NonAvailableMiners, // 6007, 0x1777
+    NonAvailableMiners, // 6006, 0x1776
     #[msg("Invalid accounts")]
-    InvalidAccounts, // 6008, 0x1778
+    InvalidAccounts, // 6007, 0x1777
     #[msg("Claim unavailable")]
-    ClaimUnavailable, // 6009, 0x1779
-    #[msg("Invalid fee account")]
-    InvalidFeeAccount, // 6010, 0x177a
+    ClaimUnavailable, // 6008, 0x1778
     #[msg("Miner frozen sells")]
-    MinerFrozenSells, // 6011, 0x177a
+    MinerFrozenSells, // 6009, 0x1779
     #[msg("Not over mine update delay")]
-    NotOverMineUpdateDelay, // 6012, 0x177b
+    NotOverMineUpdateDelay, // 6010, 0x177a
+    #[msg("Name too long")]
+    NameTooLong, // 6011, 0x177b
+    #[msg("Invalid name")]
+    InvalidName, // 6012, 0x177c
+    #[msg("Uri too long")]
+    UriTooLong, // 6013, 0x177d
+    #[msg("Invalid protocol fee")]
+    InvalidProtocolFee, // 6014, 0x177e
+    #[msg("Invalid treasury account")]
+    InvalidTreasuryAccount, // 6015, 0x177f
+    #[msg("Too many fee tiers")]
+    TooManyFeeTiers, // 6016, 0x1780
+    #[msg("Miner power too low")]
+    MinerPowerTooLow, // 6017, 0x1781
+    #[msg("Mine capacity reached")]
+    MineCapacityReached, // 6018, 0x1782
+    #[msg("Mine miners limit")]
+    MineMinersLimit, // 6019, 0x1783
+    #[msg("Not mine member")]
+    NotMineMember, // 6020, 0x1784
+    #[msg("Not collection holder")]
+    NotCollectionHolder, // 6021, 0x1785
+    #[msg("Invalid metadata account")]
+    InvalidMetadataAccount, // 6022, 0x1786
+    #[msg("Invalid sale window")]
+    InvalidSaleWindow, // 6023, 0x1787
+    #[msg("Miner sale not started")]
+    MinerSaleNotStarted, // 6024, 0x1788
+    #[msg("Miner sale ended")]
+    MinerSaleEnded, // 6025, 0x1789
+    #[msg("Miner wallet limit")]
+    MinerWalletLimit, // 6026, 0x178a
+    #[msg("Miner price above max")]
+    MinerPriceAboveMax, // 6027, 0x178b
+    #[msg("Math overflow")]
+    MathOverflow, // 6028, 0x178c
+    #[msg("Invalid power multiplier")]
+    InvalidPowerMultiplier, // 6029, 0x178d
+    #[msg("Nothing to harvest")]
+    NothingToHarvest, // 6030, 0x178e
+    #[msg("Miner already mining")]
+    MinerAlreadyMining, // 6031, 0x178f
+    #[msg("Invalid keeper tip")]
+    InvalidKeeperTip, // 6032, 0x1790
+    #[msg("Auto claim unavailable")]
+    AutoClaimUnavailable, // 6033, 0x1791
+    #[msg("Invalid cancel fee")]
+    InvalidCancelFee, // 6034, 0x1792
+    #[msg("X shares below min")]
+    XSharesBelowMin, // 6035, 0x1793
+    #[msg("Too many fee splits")]
+    TooManyFeeSplits, // 6036, 0x1794
+    #[msg("Invalid fee splits")]
+    InvalidFeeSplits, // 6037, 0x1795
+    #[msg("Mine fees split")]
+    MineFeesSplit, // 6038, 0x1796
+    #[msg("Config change not ready")]
+    ConfigChangeNotReady, // 6039, 0x1797
+    #[msg("Unknown account version")]
+    UnknownAccountVersion, // 6040, 0x1798
+    #[msg("Invalid mine access")]
+    InvalidMineAccess, // 6041, 0x1799
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Discriminator;
+
+    // lays out a legacy account the way it sits on chain once grown to `len`
+    fn legacy_data<T: AnchorSerialize>(discriminator: [u8; 8], legacy: &T, len: usize) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        data.resize(len, 0);
+        data
+    }
+
+    fn current_data<T: AccountSerialize>(account: &T, len: usize) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data.resize(len, 0);
+        data
+    }
+
+    #[test]
+    fn upgrades_legacy_config_account() {
+        let admin_key = Pubkey::new_unique();
+        let data = legacy_data(
+            ConfigAccount::discriminator(),
+            &LegacyConfigAccount {
+                admin_key,
+                freeze_program: true,
+                min_mine_fee: 1_000,
+                max_mine_fee: 5_000,
+                mine_update_delay: 7,
+            },
+            ConfigAccount::LEN,
+        );
+
+        let account = ConfigAccount::upgrade(&data, ConfigAccount::LEGACY_LEN).unwrap();
+        assert_eq!(account.version, ConfigAccount::VERSION);
+        assert_eq!(account.admin_key, admin_key);
+        assert!(account.freeze_program);
+        assert_eq!(account.min_mine_fee, 1_000);
+        assert_eq!(account.max_mine_fee, 5_000);
+        assert_eq!(account.mine_update_delay, 7);
+        assert_eq!(account.protocol_fee, 0);
+        assert_eq!(account.treasury, Pubkey::default());
+    }
+
+    #[test]
+    fn upgrades_legacy_mine_account() {
+        let owner = Pubkey::new_unique();
+        let share = AuryShare {
+            timestamp: 1,
+            token_amount: 2,
+            x_token_amount: 3,
+        };
+        let data = legacy_data(
+            MineAccount::discriminator(),
+            &LegacyMineAccount {
+                owner,
+                name: "Mine-A".to_string(),
+                fee: 2_000,
+                fee_to: Pubkey::new_unique(),
+                total_amount: 10,
+                x_total_amount: 20,
+                last_updated_at: 30,
+                shares: vec![share; constants::SHARES_LIMIT],
+            },
+            MineAccount::LEN,
+        );
+
+        let account = MineAccount::upgrade(&data, MineAccount::LEGACY_LEN).unwrap();
+        assert_eq!(account.version, MineAccount::VERSION);
+        assert_eq!(account.owner, owner);
+        assert_eq!(account.name, "Mine-A");
+        assert_eq!(account.fee, 2_000);
+        assert_eq!(account.total_amount, 10);
+        assert_eq!(account.x_total_amount, 20);
+        assert_eq!(account.last_updated_at, 30);
+        assert_eq!(account.shares.len(), constants::SHARES_LIMIT);
+        assert_eq!(account.shares[0].x_token_amount, 3);
+        assert_eq!(account.uri, "");
+        assert_eq!(account.accrued_fees, 0);
+    }
+
+    #[test]
+    fn upgrades_legacy_miner_account() {
+        let data = legacy_data(
+            MinerAccount::discriminator(),
+            &LegacyMinerAccount {
+                name: "Miner-A".to_string(),
+                cost: 10_000_000_000,
+                duration: 2,
+                limit: 3,
+                total_purchased: 1,
+                frozen_sales: true,
+            },
+            MinerAccount::LEN,
+        );
+
+        let account = MinerAccount::upgrade(&data, MinerAccount::LEGACY_LEN).unwrap();
+        assert_eq!(account.version, MinerAccount::VERSION);
+        assert_eq!(account.name, "Miner-A");
+        assert_eq!(account.cost, 10_000_000_000);
+        assert_eq!(account.duration, 2);
+        assert_eq!(account.limit, 3);
+        assert_eq!(account.total_purchased, 1);
+        assert!(account.frozen_sales);
+        assert_eq!(account.uri, "");
+        assert_eq!(account.max_per_wallet, 0);
+    }
+
+    #[test]
+    fn upgrades_legacy_user_miner_account() {
+        let owner = Pubkey::new_unique();
+        let miner_type = Pubkey::new_unique();
+        let mine_key = Pubkey::new_unique();
+        let data = legacy_data(
+            UserMinerAccount::discriminator(),
+            &LegacyUserMinerAccount {
+                owner,
+                miner_type,
+                power: 10,
+                duration: 2,
+                mining_start_at: 100,
+                mine_key,
+                x_aury_amount: 40,
+            },
+            UserMinerAccount::LEN,
+        );
+
+        let account = UserMinerAccount::upgrade(&data, UserMinerAccount::LEGACY_LEN).unwrap();
+        assert_eq!(account.version, UserMinerAccount::VERSION);
+        assert_eq!(account.owner, owner);
+        assert_eq!(account.miner_type, miner_type);
+        assert_eq!(account.power, 10);
+        assert_eq!(account.duration, 2);
+        assert_eq!(account.mining_start_at, 100);
+        assert_eq!(account.mine_key, mine_key);
+        assert_eq!(account.x_aury_amount, 40);
+        assert!(!account.fee_locked);
+        assert_eq!(account.position_mint, Pubkey::default());
+    }
+
+    #[test]
+    fn keeps_current_accounts_and_rejects_unknown_versions() {
+        let mut account = MinerAccount {
+            version: MinerAccount::VERSION,
+            name: "Miner-A".to_string(),
+            uri: "https://aurory.io/miners/miner.json".to_string(),
+            ..Default::default()
+        };
+        let data = current_data(&account, MinerAccount::LEN);
+
+        let upgraded = MinerAccount::upgrade(&data, MinerAccount::LEN).unwrap();
+        assert_eq!(upgraded.name, account.name);
+        assert_eq!(upgraded.uri, account.uri);
+
+        account.version = MinerAccount::VERSION + 1;
+        let data = current_data(&account, MinerAccount::LEN);
+        assert!(MinerAccount::upgrade(&data, MinerAccount::LEN).is_err());
+    }
 }
@@ -1,15 +1,35 @@
 use {
     crate::*,
     anchor_lang::{
-        prelude::{AccountInfo, ProgramResult},
-        solana_program::program::invoke_signed,
+        prelude::{AccountInfo, ProgramResult, Pubkey},
+        solana_program::{
+            program::{invoke, invoke_signed},
+            system_instruction,
+            sysvar::{rent::Rent, Sysvar},
+        },
+        AccountSerialize, Discriminator,
     },
+    anchor_spl::token::TokenAccount,
+    mpl_token_metadata::{
+        instruction::{
+            create_master_edition_v3, create_metadata_accounts_v2, update_metadata_accounts_v2,
+        },
+        state::{DataV2, Metadata, PREFIX},
+    },
+    std::{convert::TryInto, io::Cursor},
 };
 
+pub const BPS_MULTIPLIER: u64 = 10000; // 100%
+pub const PRICE_SCALE: u128 = 1_000_000_000_000;
+
 /// TokenTransferParams
 pub struct TokenTransferParams<'a: 'b, 'b> {
     /// source
     pub source: AccountInfo<'a>,
+    /// mint
+    pub mint: AccountInfo<'a>,
+    /// decimals
+    pub decimals: u8,
     /// destination
     pub destination: AccountInfo<'a>,
     /// amount
@@ -22,9 +42,15 @@ pub struct TokenTransferParams<'a: 'b, 'b> {
     pub token_program: AccountInfo<'a>,
 }
 
+/// Transfers with `transfer_checked`, so `authority` may be the owner of
+/// `source` or a delegate within its approved allowance. Token program errors
+/// are passed through as is. Only the spl-token program is supported, token
+/// 2022 mints would also need their extensions handled.
 pub fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult {
     let TokenTransferParams {
         source,
+        mint,
+        decimals,
         destination,
         authority,
         token_program,
@@ -32,18 +58,685 @@ pub fn spl_token_transfer(params: TokenTransferParams<'_, '_>) -> ProgramResult
         authority_signer_seeds,
     } = params;
 
-    let result = invoke_signed(
-        &spl_token::instruction::transfer(
+    invoke_signed(
+        &spl_token::instruction::transfer_checked(
             token_program.key,
             source.key,
+            mint.key,
             destination.key,
             authority.key,
             &[],
             amount,
+            decimals,
         )?,
-        &[source, destination, authority, token_program],
+        &[source, mint, destination, authority, token_program],
+        &[authority_signer_seeds],
+    )
+}
+
+/// TokenMintToParams
+pub struct TokenMintToParams<'a: 'b, 'b> {
+    /// mint
+    pub mint: AccountInfo<'a>,
+    /// destination
+    pub destination: AccountInfo<'a>,
+    /// amount
+    pub amount: u64,
+    /// authority
+    pub authority: AccountInfo<'a>,
+    /// authority_signer_seeds
+    pub authority_signer_seeds: &'b [&'b [u8]],
+    /// token_program
+    pub token_program: AccountInfo<'a>,
+}
+
+pub fn spl_token_mint_to(params: TokenMintToParams<'_, '_>) -> ProgramResult {
+    let TokenMintToParams {
+        mint,
+        destination,
+        authority,
+        token_program,
+        amount,
+        authority_signer_seeds,
+    } = params;
+
+    invoke_signed(
+        &spl_token::instruction::mint_to(
+            token_program.key,
+            mint.key,
+            destination.key,
+            authority.key,
+            &[],
+            amount,
+        )?,
+        &[mint, destination, authority, token_program],
+        &[authority_signer_seeds],
+    )
+}
+
+/// TokenBurnParams
+pub struct TokenBurnParams<'a: 'b, 'b> {
+    /// mint
+    pub mint: AccountInfo<'a>,
+    /// source
+    pub source: AccountInfo<'a>,
+    /// amount
+    pub amount: u64,
+    /// authority
+    pub authority: AccountInfo<'a>,
+    /// authority_signer_seeds
+    pub authority_signer_seeds: &'b [&'b [u8]],
+    /// token_program
+    pub token_program: AccountInfo<'a>,
+}
+
+pub fn spl_token_burn(params: TokenBurnParams<'_, '_>) -> ProgramResult {
+    let TokenBurnParams {
+        mint,
+        source,
+        authority,
+        token_program,
+        amount,
+        authority_signer_seeds,
+    } = params;
+
+    invoke_signed(
+        &spl_token::instruction::burn(
+            token_program.key,
+            source.key,
+            mint.key,
+            authority.key,
+            &[],
+            amount,
+        )?,
+        &[source, mint, authority, token_program],
+        &[authority_signer_seeds],
+    )
+}
+
+/// TokenApproveParams
+pub struct TokenApproveParams<'a> {
+    /// source
+    pub source: AccountInfo<'a>,
+    /// delegate
+    pub delegate: AccountInfo<'a>,
+    /// amount
+    pub amount: u64,
+    /// authority
+    pub authority: AccountInfo<'a>,
+    /// token_program
+    pub token_program: AccountInfo<'a>,
+}
+
+pub fn spl_token_approve(params: TokenApproveParams<'_>) -> ProgramResult {
+    let TokenApproveParams {
+        source,
+        delegate,
+        amount,
+        authority,
+        token_program,
+    } = params;
+
+    invoke(
+        &spl_token::instruction::approve(
+            token_program.key,
+            source.key,
+            delegate.key,
+            authority.key,
+            &[],
+            amount,
+        )?,
+        &[source, delegate, authority, token_program],
+    )
+}
+
+/// TokenRevokeParams
+pub struct TokenRevokeParams<'a> {
+    /// source
+    pub source: AccountInfo<'a>,
+    /// authority
+    pub authority: AccountInfo<'a>,
+    /// token_program
+    pub token_program: AccountInfo<'a>,
+}
+
+pub fn spl_token_revoke(params: TokenRevokeParams<'_>) -> ProgramResult {
+    let TokenRevokeParams {
+        source,
+        authority,
+        token_program,
+    } = params;
+
+    invoke(
+        &spl_token::instruction::revoke(token_program.key, source.key, authority.key, &[])?,
+        &[source, authority, token_program],
+    )
+}
+
+/// PositionNftParams
+pub struct PositionNftParams<'a: 'b, 'b> {
+    /// mint
+    pub mint: AccountInfo<'a>,
+    /// destination
+    pub destination: AccountInfo<'a>,
+    /// metadata
+    pub metadata: AccountInfo<'a>,
+    /// master_edition
+    pub master_edition: AccountInfo<'a>,
+    /// name
+    pub name: String,
+    /// uri
+    pub uri: String,
+    /// payer
+    pub payer: AccountInfo<'a>,
+    /// authority, of both the mint and the metadata
+    pub authority: AccountInfo<'a>,
+    /// authority_signer_seeds
+    pub authority_signer_seeds: &'b [&'b [u8]],
+    /// token_metadata_program
+    pub token_metadata_program: AccountInfo<'a>,
+    /// token_program
+    pub token_program: AccountInfo<'a>,
+    /// system_program
+    pub system_program: AccountInfo<'a>,
+    /// rent
+    pub rent: AccountInfo<'a>,
+}
+
+/// Mints the one token of a position nft to `destination`, describes it with
+/// `name` and `uri` and caps its supply with a master edition. The metadata
+/// stays mutable by `authority` so it can follow the position.
+pub fn mint_position_nft(params: PositionNftParams<'_, '_>) -> ProgramResult {
+    let PositionNftParams {
+        mint,
+        destination,
+        metadata,
+        master_edition,
+        name,
+        uri,
+        payer,
+        authority,
+        authority_signer_seeds,
+        token_metadata_program,
+        token_program,
+        system_program,
+        rent,
+    } = params;
+
+    spl_token_mint_to(TokenMintToParams {
+        mint: mint.clone(),
+        destination,
+        amount: 1,
+        authority: authority.clone(),
+        authority_signer_seeds,
+        token_program: token_program.clone(),
+    })?;
+
+    invoke_signed(
+        &create_metadata_accounts_v2(
+            *token_metadata_program.key,
+            *metadata.key,
+            *mint.key,
+            *authority.key,
+            *payer.key,
+            *authority.key,
+            position_nft_name(&name),
+            constants::MINER_NFT_SYMBOL.to_string(),
+            uri,
+            None,
+            0,
+            true,
+            true,
+            None,
+            None,
+        ),
+        &[
+            metadata.clone(),
+            mint.clone(),
+            authority.clone(),
+            payer.clone(),
+            authority.clone(),
+            system_program.clone(),
+            rent.clone(),
+        ],
+        &[authority_signer_seeds],
+    )?;
+
+    invoke_signed(
+        &create_master_edition_v3(
+            *token_metadata_program.key,
+            *master_edition.key,
+            *mint.key,
+            *authority.key,
+            *authority.key,
+            *metadata.key,
+            *payer.key,
+            Some(0),
+        ),
+        &[
+            master_edition,
+            mint,
+            authority.clone(),
+            authority,
+            payer,
+            metadata,
+            token_program,
+            system_program,
+            rent,
+        ],
         &[authority_signer_seeds],
+    )
+}
+
+/// PurchaseParams
+pub struct PurchaseParams<'a: 'b, 'b> {
+    /// miner_key
+    pub miner_key: Pubkey,
+    /// miner_account
+    pub miner_account: &'b mut MinerAccount,
+    /// user_miner_account, the position being bought
+    pub user_miner_account: &'b mut UserMinerAccount,
+    /// wallet_purchase_account
+    pub wallet_purchase_account: &'b mut WalletPurchaseAccount,
+    /// amount
+    pub amount: u64,
+    /// max_total_cost
+    pub max_total_cost: u64,
+    /// buyer, the owner of `aury_from`
+    pub buyer: Pubkey,
+    /// proofs, the miner's collection proofs
+    pub proofs: &'b [AccountInfo<'a>],
+    /// aury_from
+    pub aury_from: AccountInfo<'a>,
+    /// aury_from_authority, also pays for the position nft
+    pub aury_from_authority: AccountInfo<'a>,
+    /// aury_mint
+    pub aury_mint: AccountInfo<'a>,
+    /// aury_decimals
+    pub aury_decimals: u8,
+    /// aury_vault
+    pub aury_vault: AccountInfo<'a>,
+    /// position_mint
+    pub position_mint: AccountInfo<'a>,
+    /// position_token_account
+    pub position_token_account: AccountInfo<'a>,
+    /// metadata
+    pub metadata: AccountInfo<'a>,
+    /// master_edition
+    pub master_edition: AccountInfo<'a>,
+    /// authority, the config account
+    pub authority: AccountInfo<'a>,
+    /// authority_signer_seeds
+    pub authority_signer_seeds: &'b [&'b [u8]],
+    /// token_metadata_program
+    pub token_metadata_program: AccountInfo<'a>,
+    /// token_program
+    pub token_program: AccountInfo<'a>,
+    /// system_program
+    pub system_program: AccountInfo<'a>,
+    /// rent
+    pub rent: AccountInfo<'a>,
+}
+
+/// Sells `amount` miners to `buyer` as a new position: checks the sale, takes
+/// the price into the vault and mints the position nft.
+pub fn purchase_position(params: PurchaseParams<'_, '_>) -> ProgramResult {
+    let PurchaseParams {
+        miner_key,
+        miner_account,
+        user_miner_account,
+        wallet_purchase_account,
+        amount,
+        max_total_cost,
+        buyer,
+        proofs,
+        aury_from,
+        aury_from_authority,
+        aury_mint,
+        aury_decimals,
+        aury_vault,
+        position_mint,
+        position_token_account,
+        metadata,
+        master_edition,
+        authority,
+        authority_signer_seeds,
+        token_metadata_program,
+        token_program,
+        system_program,
+        rent,
+    } = params;
+
+    miner_account.assert_purchasable(amount, wallet_purchase_account.purchased)?;
+    miner_account.assert_buyer(&buyer, proofs)?;
+
+    // transfer aury to the vault
+    let power = miner_account.price_for(amount)?;
+    if power > max_total_cost {
+        return Err(ErrorCode::MinerPriceAboveMax.into());
+    }
+
+    spl_token_transfer(TokenTransferParams {
+        source: aury_from,
+        mint: aury_mint,
+        decimals: aury_decimals,
+        destination: aury_vault,
+        amount: power,
+        authority: aury_from_authority.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program.clone(),
+    })?;
+
+    // update the user miner
+    user_miner_account.version = UserMinerAccount::VERSION;
+    user_miner_account.owner = buyer;
+    user_miner_account.miner_type = miner_key;
+    user_miner_account.power = power;
+    user_miner_account.duration = miner_account.duration;
+    user_miner_account.power_multiplier = miner_account.power_multiplier;
+    user_miner_account.amount = amount;
+    user_miner_account.position_mint = *position_mint.key;
+
+    // mint the position nft to the buyer, whoever holds it controls the
+    // miner
+    mint_position_nft(PositionNftParams {
+        mint: position_mint,
+        destination: position_token_account,
+        metadata,
+        master_edition,
+        name: miner_account.name.clone(),
+        uri: miner_account.uri.clone(),
+        payer: aury_from_authority,
+        authority,
+        authority_signer_seeds,
+        token_metadata_program,
+        token_program,
+        system_program,
+        rent,
+    })?;
+
+    // update the miner_account
+    miner_account.total_purchased += amount;
+
+    // update the wallet purchases
+    wallet_purchase_account.record(miner_key, buyer, amount);
+
+    Ok(())
+}
+
+/// Renames the position nft minted as `mint` after the miner type it was
+/// restaked into. `metadata` must be the nft's metadata account.
+pub fn update_position_nft<'a>(
+    mint: &Pubkey,
+    metadata: &AccountInfo<'a>,
+    name: &str,
+    uri: &str,
+    authority: &AccountInfo<'a>,
+    authority_signer_seeds: &[&[u8]],
+    token_metadata_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    let (metadata_key, _) = Pubkey::find_program_address(
+        &[
+            PREFIX.as_bytes(),
+            token_metadata_program.key.as_ref(),
+            mint.as_ref(),
+        ],
+        token_metadata_program.key,
+    );
+    if *metadata.key != metadata_key {
+        return Err(ErrorCode::InvalidMetadataAccount.into());
+    }
+
+    invoke_signed(
+        &update_metadata_accounts_v2(
+            *token_metadata_program.key,
+            *metadata.key,
+            *authority.key,
+            None,
+            Some(DataV2 {
+                name: position_nft_name(name),
+                symbol: constants::MINER_NFT_SYMBOL.to_string(),
+                uri: uri.to_string(),
+                seller_fee_basis_points: 0,
+                creators: None,
+                collection: None,
+                uses: None,
+            }),
+            None,
+            None,
+        ),
+        &[metadata.clone(), authority.clone()],
+        &[authority_signer_seeds],
+    )
+}
+
+/// Position nfts are named after their miner type, cut to the metadata's
+/// name limit.
+fn position_nft_name(name: &str) -> String {
+    name.chars()
+        .take(constants::MINER_NFT_NAME_MAX_LEN)
+        .collect()
+}
+
+/// Checks that `account` is owned by this program and holds a `T`,
+/// whatever layout version it was written with.
+pub fn assert_program_account<T: Discriminator>(
+    account: &AccountInfo,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let data = account.try_borrow_data()?;
+
+    if account.owner != program_id || data.len() < 8 || data[..8] != T::discriminator() {
+        return Err(ErrorCode::InvalidAccounts.into());
+    }
+
+    Ok(())
+}
+
+/// Grows `account` to `new_len` bytes, topping up its rent from `payer`.
+pub fn realloc_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    new_len: usize,
+) -> ProgramResult {
+    if account.data_len() >= new_len {
+        return Ok(());
+    }
+
+    let lamports_needed = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(account.lamports());
+    if lamports_needed > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, lamports_needed),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+
+    account.realloc(new_len, true)
+}
+
+/// Writes `account_data`, discriminator included, into `account`.
+pub fn write_account<T: AccountSerialize>(
+    account: &AccountInfo,
+    account_data: &T,
+) -> ProgramResult {
+    let mut data = account.try_borrow_mut_data()?;
+    let dst: &mut [u8] = &mut data;
+    let mut cursor = Cursor::new(dst);
+
+    account_data.try_serialize(&mut cursor)
+}
+
+/// Creates `account` at the program address derived from `signer_seeds`,
+/// funded by `payer`.
+pub fn create_program_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    signer_seeds: &[&[u8]],
+    len: usize,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let lamports = Rent::get()?.minimum_balance(len);
+
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            account.key,
+            lamports,
+            len as u64,
+            program_id,
+        ),
+        &[payer.clone(), account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )
+}
+
+/// Moves all of `account`'s lamports to `destination` and wipes its data, so
+/// the runtime drops it at the end of the transaction.
+pub fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? = destination
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(ErrorCode::MathOverflow)?;
+    **account.try_borrow_mut_lamports()? = 0;
+    account.try_borrow_mut_data()?.fill(0);
+
+    Ok(())
+}
+
+/// Names are limited to `NAME_MAX_LEN` bytes of plain ASCII letters, digits,
+/// spaces, `-`, `_` and `.`, without leading or trailing spaces, so that
+/// look-alike characters can't be used to impersonate official mines.
+pub fn assert_valid_name(name: &str) -> ProgramResult {
+    if name.len() > constants::NAME_MAX_LEN {
+        return Err(ErrorCode::NameTooLong.into());
+    }
+
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.');
+    if name.is_empty() || name.trim() != name || !name.chars().all(allowed) {
+        return Err(ErrorCode::InvalidName.into());
+    }
+
+    Ok(())
+}
+
+pub fn assert_valid_uri(uri: &str) -> ProgramResult {
+    if uri.len() > constants::URI_MAX_LEN {
+        return Err(ErrorCode::UriTooLong.into());
+    }
+
+    Ok(())
+}
+
+/// Checks that `token_account` belongs to `owner` and holds an NFT whose
+/// `metadata` is a verified member of `collection`.
+pub fn assert_collection_holder(
+    owner: &Pubkey,
+    token_account: &TokenAccount,
+    metadata: &AccountInfo,
+    collection: &Pubkey,
+) -> ProgramResult {
+    if !(token_account.owner == *owner && token_account.amount > 0) {
+        return Err(ErrorCode::NotCollectionHolder.into());
+    }
+
+    let metadata_program_id = mpl_token_metadata::id();
+    let (metadata_key, _) = Pubkey::find_program_address(
+        &[
+            PREFIX.as_bytes(),
+            metadata_program_id.as_ref(),
+            token_account.mint.as_ref(),
+        ],
+        &metadata_program_id,
     );
+    if !(*metadata.key == metadata_key && *metadata.owner == metadata_program_id) {
+        return Err(ErrorCode::InvalidMetadataAccount.into());
+    }
+
+    let metadata = Metadata::from_account_info(metadata)?;
+    match metadata.collection {
+        Some(c) if c.verified && c.key == *collection => Ok(()),
+        _ => Err(ErrorCode::NotCollectionHolder.into()),
+    }
+}
+
+/// `(1 + step_bps / 10000) ^ exp`, scaled by `PRICE_SCALE`. `None` on overflow.
+pub fn pow_bps(step_bps: u64, mut exp: u64) -> Option<u128> {
+    let mut base = (BPS_MULTIPLIER + step_bps) as u128 * PRICE_SCALE / BPS_MULTIPLIER as u128;
+    let mut result = PRICE_SCALE;
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result.checked_mul(base)? / PRICE_SCALE;
+        }
+        exp >>= 1;
+        if exp > 0 {
+            base = base.checked_mul(base)? / PRICE_SCALE;
+        }
+    }
+
+    Some(result)
+}
+
+/// X-shares worth `amount` in a mine holding `token_amount` for
+/// `x_token_amount` x-shares. Both are offset by `VIRTUAL_X_SHARES`, so a tiny
+/// first miner followed by a large reward can't push the share price high
+/// enough to round later miners down to nothing.
+pub fn x_shares_for(amount: u64, token_amount: u64, x_token_amount: u64, round_up: bool) -> u64 {
+    let x_token_amount = x_token_amount as u128 + constants::VIRTUAL_X_SHARES as u128;
+    let token_amount = token_amount as u128 + constants::VIRTUAL_X_SHARES as u128;
+    let round = if round_up { token_amount - 1 } else { 0 };
+
+    (amount as u128)
+        .checked_mul(x_token_amount)
+        .unwrap()
+        .checked_add(round)
+        .unwrap()
+        .checked_div(token_amount)
+        .unwrap()
+        .try_into()
+        .unwrap()
+}
+
+/// Value of `x_amount` x-shares in a mine holding `token_amount` for
+/// `x_token_amount` x-shares, with the same offset as `x_shares_for`.
+pub fn x_shares_value(x_amount: u64, token_amount: u64, x_token_amount: u64) -> u64 {
+    (x_amount as u128)
+        .checked_mul(token_amount as u128 + constants::VIRTUAL_X_SHARES as u128)
+        .unwrap()
+        .checked_div(x_token_amount as u128 + constants::VIRTUAL_X_SHARES as u128)
+        .unwrap()
+        .try_into()
+        .unwrap()
+}
+
+/// Splits a miner's `reward` into what the miner keeps, the protocol's cut of
+/// the mine fee and what's left of the mine fee, with both fees in bps.
+pub fn split_reward(reward: u64, fee: u64, protocol_fee: u64) -> (u64, u64, u64) {
+    let reward_amount: u64 = (reward as u128)
+        .checked_mul((BPS_MULTIPLIER - fee) as u128)
+        .unwrap()
+        .checked_div(BPS_MULTIPLIER as u128)
+        .unwrap()
+        .try_into()
+        .unwrap();
+
+    let fee_amount = reward - reward_amount;
+    let protocol_fee_amount: u64 = (fee_amount as u128)
+        .checked_mul(protocol_fee as u128)
+        .unwrap()
+        .checked_div(BPS_MULTIPLIER as u128)
+        .unwrap()
+        .try_into()
+        .unwrap();
 
-    result.map_err(|_| ErrorCode::TokenTransferFailed.into())
+    (
+        reward_amount,
+        protocol_fee_amount,
+        fee_amount - protocol_fee_amount,
+    )
 }
@@ -0,0 +1,17 @@
+#!/usr/bin/env bash
+# Builds the token metadata program that `anchor test` loads at genesis, at
+# the version mine-together is pinned to. Mainnet runs a newer release that
+# no longer accepts the metadata instructions used here, so it can't be dumped.
+set -euo pipefail
+
+VERSION=1.2.5
+ROOT="$(cd "$(dirname "$0")/.." && pwd)"
+WORK="$(mktemp -d)"
+trap 'rm -rf "$WORK"' EXIT
+
+curl -sSfL "https://static.crates.io/crates/mpl-token-metadata/${VERSION}/download" |
+  tar -xz -C "$WORK"
+cd "$WORK/mpl-token-metadata-${VERSION}"
+
+mkdir -p "$ROOT/tests/deps"
+cargo build-bpf --bpf-out-dir "$ROOT/tests/deps"
@@ -3,7 +3,11 @@ import fs from 'fs';
 import assert from 'assert';
 import { expect } from 'chai';
 import { PublicKey } from '@solana/web3.js';
-import { TOKEN_PROGRAM_ID, Token } from '@solana/spl-token';
+import {
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  Token,
+} from '@solana/spl-token';
 import { web3, Program } from '@project-serum/anchor';
 import { MineTogether } from '../target/types/mine_together';
 import {
@@ -15,6 +19,10 @@ import {
 
 let program = anchor.workspace.MineTogether as Program<MineTogether>;
 
+const TOKEN_METADATA_PROGRAM_ID = new PublicKey(
+  'metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s'
+);
+
 const envProvider = anchor.Provider.env();
 
 let provider = envProvider;
@@ -42,6 +50,12 @@ describe('mine-together', () => {
   let aliceAuryTokenAccount: PublicKey;
   let aliceUserMinerPubkey: PublicKey[] = [];
   let aliceUserMinerBump: number[] = [];
+  let alicePositionMint: web3.Keypair[] = [];
+  let alicePositionTokenAccount: PublicKey[] = [];
+  let alicePositionMetadata: PublicKey[] = [];
+  let alicePositionMasterEdition: PublicKey[] = [];
+  let aliceWalletPurchasePubkey: PublicKey[] = [];
+  let aliceWalletPurchaseBump: number[] = [];
 
   // Bob
   const bob = web3.Keypair.generate();
@@ -51,6 +65,8 @@ describe('mine-together', () => {
   // Config
   let configPubkey: PublicKey;
   let configBump: number;
+  let pendingConfigChangePubkey: PublicKey;
+  let pendingConfigChangeBump: number;
   const minMineFee = new anchor.BN(1000); // 10%
   const maxMineFee = new anchor.BN(5000); // 50%
   const mineUpdateDelay = new anchor.BN(7); // 7 seconds
@@ -58,13 +74,18 @@ describe('mine-together', () => {
   // Mine
   let minePubkey: PublicKey;
   let mineBump: number;
+  let mineFeesPubkey: PublicKey;
+  let mineFeesBump: number;
   let mineName = 'Mine-A';
   let mineFee = new anchor.BN(2000); // 20%
+  const mineUri = 'https://aurory.io/mines/mine-a.json';
 
   // Miner
   let minerPubkey: PublicKey[] = [];
   let minerBump: number[] = [];
   let minerCount = 3;
+  const minerUri = 'https://aurory.io/miners/miner.json';
+  const maxTotalCost = new anchor.BN(1_000_000_000_000);
   const minerName = [
     'Miner-A',
     'Miner-B',
@@ -102,6 +123,143 @@ describe('mine-together', () => {
     new anchor.BN(0),
   ];
 
+  // Every purchase mints a new position nft, the position is keyed on it
+  async function newPosition(i: number) {
+    const mint = web3.Keypair.generate();
+    alicePositionMint[i] = mint;
+
+    [aliceUserMinerPubkey[i], aliceUserMinerBump[i]] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          minerPubkey[i].toBuffer(),
+          Buffer.from(anchor.utils.bytes.utf8.encode('MINE_TOGETHER_MINER')),
+          mint.publicKey.toBuffer(),
+        ],
+        program.programId
+      );
+
+    alicePositionTokenAccount[i] = await Token.getAssociatedTokenAddress(
+      ASSOCIATED_TOKEN_PROGRAM_ID,
+      TOKEN_PROGRAM_ID,
+      mint.publicKey,
+      alicePubkey
+    );
+
+    [alicePositionMetadata[i]] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from(anchor.utils.bytes.utf8.encode('metadata')),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        mint.publicKey.toBuffer(),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+
+    [alicePositionMasterEdition[i]] =
+      await anchor.web3.PublicKey.findProgramAddress(
+        [
+          Buffer.from(anchor.utils.bytes.utf8.encode('metadata')),
+          TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+          mint.publicKey.toBuffer(),
+          Buffer.from(anchor.utils.bytes.utf8.encode('edition')),
+        ],
+        TOKEN_METADATA_PROGRAM_ID
+      );
+  }
+
+  function positionAccounts(i: number) {
+    return {
+      positionMint: alicePositionMint[i].publicKey,
+      auryFromOwner: alicePubkey,
+      positionTokenAccount: alicePositionTokenAccount[i],
+      metadata: alicePositionMetadata[i],
+      masterEdition: alicePositionMasterEdition[i],
+      tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+    };
+  }
+
+  // The token account proving alice holds position i, followed by its mint
+  // when the nft is burned with the position
+  function positionProofs(i: number, burn = false) {
+    const proofs = [
+      {
+        pubkey: alicePositionTokenAccount[i],
+        isWritable: burn,
+        isSigner: false,
+      },
+    ];
+    if (burn) {
+      proofs.push({
+        pubkey: alicePositionMint[i].publicKey,
+        isWritable: true,
+        isSigner: false,
+      });
+    }
+
+    return proofs;
+  }
+
+  // Pending config changes are keyed on the kind of change
+  const configChangeKinds = [
+    'mine',
+    'admin',
+    'protocolFee',
+    'keeperTip',
+    'cancelFee',
+  ];
+
+  async function findPendingConfigChange(kind: string) {
+    return await anchor.web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from(
+          anchor.utils.bytes.utf8.encode('MINE_TOGETHER_PENDING_CONFIG_CHANGE')
+        ),
+        Buffer.from([configChangeKinds.indexOf(kind)]),
+      ],
+      program.programId
+    );
+  }
+
+  async function queueConfigChange(change, treasury: PublicKey[] = []) {
+    const [pendingConfigChange, pendingConfigChangeBump] =
+      await findPendingConfigChange(Object.keys(change)[0]);
+    await program.rpc.queueConfigChange(
+      configBump,
+      pendingConfigChangeBump,
+      change,
+      {
+        accounts: {
+          configAccount: configPubkey,
+          pendingConfigChange,
+          admin: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        },
+        remainingAccounts: treasury.map((pubkey) => ({
+          pubkey,
+          isWritable: false,
+          isSigner: false,
+        })),
+      }
+    );
+  }
+
+  async function executeConfigChange(kind: string) {
+    const [pendingConfigChange, pendingConfigChangeBump] =
+      await findPendingConfigChange(kind);
+    await program.rpc.executeConfigChange(
+      configBump,
+      pendingConfigChangeBump,
+      {
+        accounts: {
+          configAccount: configPubkey,
+          pendingConfigChange,
+          admin: provider.wallet.publicKey,
+        },
+      }
+    );
+  }
+
   describe('Initialize & UpdateConfig', () => {
     it('Prepare Aury', async () => {
       // Aury MintAccount
@@ -191,6 +349,7 @@ describe('mine-together', () => {
       const configAccount = await program.account.configAccount.fetch(
         configPubkey
       );
+      assert.equal(configAccount.version, 1);
       assert.equal(
         configAccount.adminKey.toString(),
         provider.wallet.publicKey.toString()
@@ -204,15 +363,87 @@ describe('mine-together', () => {
       );
     });
 
-    it('Update Config Mine', async () => {
-      await program.rpc.updateConfigMine(
+    it('Migrate config (already current)', async () => {
+      await program.rpc.migrateConfig(configBump, {
+        accounts: {
+          configAccount: configPubkey,
+          admin: provider.wallet.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        },
+      });
+
+      const configAccount = await program.account.configAccount.fetch(
+        configPubkey
+      );
+      assert.equal(configAccount.version, 1);
+      assert.equal(
+        configAccount.adminKey.toString(),
+        provider.wallet.publicKey.toString()
+      );
+    });
+
+    it('Queue Config Mine', async () => {
+      [pendingConfigChangePubkey, pendingConfigChangeBump] =
+        await findPendingConfigChange('mine');
+
+      await program.rpc.queueConfigChange(
         configBump,
-        minMineFee,
-        maxMineFee,
-        mineUpdateDelay,
+        pendingConfigChangeBump,
+        { mine: { minMineFee, maxMineFee, mineUpdateDelay } },
+        {
+          accounts: {
+            configAccount: configPubkey,
+            pendingConfigChange: pendingConfigChangePubkey,
+            admin: provider.wallet.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          },
+        }
+      );
+
+      const pendingConfigChange =
+        await program.account.pendingConfigChange.fetch(
+          pendingConfigChangePubkey
+        );
+      assert.equal(
+        pendingConfigChange.executeAt.toNumber() -
+          pendingConfigChange.queuedAt.toNumber(),
+        3
+      );
+    });
+
+    it('Execute Config Mine too early - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.executeConfigChange(
+            configBump,
+            pendingConfigChangeBump,
+            {
+              accounts: {
+                configAccount: configPubkey,
+                pendingConfigChange: pendingConfigChangePubkey,
+                admin: provider.wallet.publicKey,
+              },
+            }
+          );
+        },
+        {
+          code: 6039,
+          message: '6039: Config change not ready',
+        }
+      );
+    });
+
+    it('Execute Config Mine', async () => {
+      await sleep(4000);
+
+      await program.rpc.executeConfigChange(
+        configBump,
+        pendingConfigChangeBump,
         {
           accounts: {
             configAccount: configPubkey,
+            pendingConfigChange: pendingConfigChangePubkey,
             admin: provider.wallet.publicKey,
           },
         }
@@ -233,6 +464,200 @@ describe('mine-together', () => {
         mineUpdateDelay.toNumber()
       );
     });
+
+    it('Cancel Config Admin', async () => {
+      [pendingConfigChangePubkey, pendingConfigChangeBump] =
+        await findPendingConfigChange('admin');
+
+      await program.rpc.queueConfigChange(
+        configBump,
+        pendingConfigChangeBump,
+        { admin: { newAdmin: alicePubkey } },
+        {
+          accounts: {
+            configAccount: configPubkey,
+            pendingConfigChange: pendingConfigChangePubkey,
+            admin: provider.wallet.publicKey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          },
+        }
+      );
+
+      await program.rpc.cancelConfigChange(
+        configBump,
+        pendingConfigChangeBump,
+        {
+          accounts: {
+            configAccount: configPubkey,
+            pendingConfigChange: pendingConfigChangePubkey,
+            admin: provider.wallet.publicKey,
+          },
+        }
+      );
+
+      await assert.rejects(
+        async () => {
+          await program.account.pendingConfigChange.fetch(
+            pendingConfigChangePubkey
+          );
+        },
+        {
+          message:
+            'Account does not exist ' + pendingConfigChangePubkey.toString(),
+        }
+      );
+
+      const configAccount = await program.account.configAccount.fetch(
+        configPubkey
+      );
+      assert.equal(
+        configAccount.adminKey.toString(),
+        provider.wallet.publicKey.toString()
+      );
+    });
+
+    it('Queue Config changes of different kinds side by side', async () => {
+      await queueConfigChange({ admin: { newAdmin: alicePubkey } });
+      await queueConfigChange({ keeperTip: { keeperTip: new anchor.BN(50) } });
+
+      for (const kind of ['admin', 'keeperTip']) {
+        const [pendingConfigChange, pendingConfigChangeBump] =
+          await findPendingConfigChange(kind);
+        await program.rpc.cancelConfigChange(
+          configBump,
+          pendingConfigChangeBump,
+          {
+            accounts: {
+              configAccount: configPubkey,
+              pendingConfigChange,
+              admin: provider.wallet.publicKey,
+            },
+          }
+        );
+      }
+
+      const configAccount = await program.account.configAccount.fetch(
+        configPubkey
+      );
+      assert.equal(
+        configAccount.adminKey.toString(),
+        provider.wallet.publicKey.toString()
+      );
+      assert.equal(configAccount.keeperTip.toNumber(), 0);
+    });
+
+    it('Queue Config Cancel Fee without treasury - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await queueConfigChange({
+            cancelFee: { cancelFee: new anchor.BN(100) },
+          });
+        },
+        {
+          code: 6015,
+          message: '6015: Invalid treasury account',
+        }
+      );
+    });
+
+    it('Update Config Protocol Fee', async () => {
+      await assert.rejects(
+        async () => {
+          await queueConfigChange(
+            {
+              protocolFee: {
+                protocolFee: new anchor.BN(10001),
+                treasury: aliceAuryTokenAccount,
+              },
+            },
+            [aliceAuryTokenAccount]
+          );
+        },
+        {
+          code: 6014,
+          message: '6014: Invalid protocol fee',
+        }
+      );
+
+      await assert.rejects(
+        async () => {
+          await queueConfigChange(
+            {
+              protocolFee: {
+                protocolFee: new anchor.BN(1000),
+                treasury: alicePubkey,
+              },
+            },
+            [alicePubkey]
+          );
+        },
+        {
+          code: 6015,
+          message: '6015: Invalid treasury account',
+        }
+      );
+
+      await queueConfigChange(
+        {
+          protocolFee: {
+            protocolFee: new anchor.BN(1000),
+            treasury: aliceAuryTokenAccount,
+          },
+        },
+        [aliceAuryTokenAccount]
+      );
+      await sleep(4000);
+      await executeConfigChange('protocolFee');
+
+      let configAccount = await program.account.configAccount.fetch(
+        configPubkey
+      );
+      assert.equal(configAccount.protocolFee.toNumber(), 1000);
+      assert.equal(
+        configAccount.treasury.toString(),
+        aliceAuryTokenAccount.toString()
+      );
+
+      // claims below assume no protocol fee
+      await queueConfigChange(
+        {
+          protocolFee: {
+            protocolFee: new anchor.BN(0),
+            treasury: aliceAuryTokenAccount,
+          },
+        },
+        [aliceAuryTokenAccount]
+      );
+      await sleep(4000);
+      await executeConfigChange('protocolFee');
+
+      configAccount = await program.account.configAccount.fetch(configPubkey);
+      assert.equal(configAccount.protocolFee.toNumber(), 0);
+    });
+
+    it('Update Config Keeper Tip', async () => {
+      await assert.rejects(
+        async () => {
+          await queueConfigChange({
+            keeperTip: { keeperTip: new anchor.BN(501) },
+          });
+        },
+        {
+          code: 6032,
+          message: '6032: Invalid keeper tip',
+        }
+      );
+
+      await queueConfigChange({ keeperTip: { keeperTip: new anchor.BN(100) } });
+      await sleep(4000);
+      await executeConfigChange('keeperTip');
+
+      const configAccount = await program.account.configAccount.fetch(
+        configPubkey
+      );
+      assert.equal(configAccount.keeperTip.toNumber(), 100);
+    });
   });
 
   describe('Miner', () => {
@@ -252,20 +677,20 @@ describe('mine-together', () => {
         minerPubkey.push(pubkey);
         minerBump.push(bump);
 
-        const [userMinerPubkey, userMinerBump] =
+        const [walletPurchasePubkey, walletPurchaseBump] =
           await anchor.web3.PublicKey.findProgramAddress(
             [
               pubkey.toBuffer(),
               Buffer.from(
-                anchor.utils.bytes.utf8.encode('MINE_TOGETHER_MINER')
+                anchor.utils.bytes.utf8.encode('MINE_TOGETHER_WALLET_PURCHASE')
               ),
               alicePubkey.toBuffer(),
             ],
             program.programId
           );
 
-        aliceUserMinerPubkey.push(userMinerPubkey);
-        aliceUserMinerBump.push(userMinerBump);
+        aliceWalletPurchasePubkey.push(walletPurchasePubkey);
+        aliceWalletPurchaseBump.push(walletPurchaseBump);
 
         await program.rpc.createMiner(
           configBump,
@@ -275,6 +700,7 @@ describe('mine-together', () => {
           minerCost[i],
           minerDuration[i],
           minerLimit[i],
+          minerUri,
           {
             accounts: {
               configAccount: configPubkey,
@@ -288,6 +714,7 @@ describe('mine-together', () => {
 
         const minerAccount = await program.account.minerAccount.fetch(pubkey);
         assert.equal(minerAccount.name, minerName[i]);
+        assert.equal(minerAccount.uri, minerUri);
         assert.equal(minerAccount.cost.toNumber(), minerCost[i].toNumber());
         assert.equal(
           minerAccount.duration.toNumber(),
@@ -296,6 +723,11 @@ describe('mine-together', () => {
         assert.equal(minerAccount.limit.toNumber(), minerLimit[i].toNumber());
         assert.equal(minerAccount.totalPurchased.toNumber(), 0);
         assert.equal(minerAccount.frozenSales, false);
+        assert.equal(minerAccount.powerMultiplier.toNumber(), 10000);
+        assert.equal(
+          minerAccount.requiredCollection.toString(),
+          PublicKey.default.toString()
+        );
 
         await sleep(1000);
       }
@@ -321,17 +753,66 @@ describe('mine-together', () => {
       );
     });
 
+    it('Update miner sale', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.updateMinerSale(
+            configBump,
+            new anchor.BN(10),
+            new anchor.BN(10),
+            new anchor.BN(5),
+            {
+              accounts: {
+                configAccount: configPubkey,
+                minerAccount: minerPubkey[2],
+                admin: provider.wallet.publicKey,
+              },
+            }
+          );
+        },
+        {
+          code: 6023,
+          message: '6023: Invalid sale window',
+        }
+      );
+
+      await program.rpc.updateMinerSale(
+        configBump,
+        new anchor.BN(10),
+        new anchor.BN(0),
+        new anchor.BN(0),
+        {
+          accounts: {
+            configAccount: configPubkey,
+            minerAccount: minerPubkey[2],
+            admin: provider.wallet.publicKey,
+          },
+        }
+      );
+
+      const minerAccount = await program.account.minerAccount.fetch(
+        minerPubkey[2]
+      );
+      assert.equal(minerAccount.maxPerWallet.toNumber(), 10);
+      assert.equal(minerAccount.saleStart.toNumber(), 0);
+      assert.equal(minerAccount.saleEnd.toNumber(), 0);
+    });
+
     it('Purchase unlimited miner', async () => {
+      await newPosition(0);
       await program.rpc.purchaseMiner(
         configBump,
         aliceUserMinerBump[0],
         auryVaultBump,
+        aliceWalletPurchaseBump[0],
         new anchor.BN(5),
+        maxTotalCost,
         {
           accounts: {
             configAccount: configPubkey,
             minerAccount: minerPubkey[0],
             userMinerAccount: aliceUserMinerPubkey[0],
+            walletPurchaseAccount: aliceWalletPurchasePubkey[0],
             auryMint: auryMintPubkey,
             auryVault: auryVaultPubkey,
             auryFrom: aliceAuryTokenAccount,
@@ -339,7 +820,9 @@ describe('mine-together', () => {
             systemProgram: anchor.web3.SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
             rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            ...positionAccounts(0),
           },
+          signers: [alicePositionMint[0]],
         }
       );
 
@@ -362,6 +845,17 @@ describe('mine-together', () => {
         PublicKey.default.toString()
       );
       assert.equal(userMinerAccount.xAuryAmount.toNumber(), 0);
+      assert.equal(
+        userMinerAccount.positionMint.toString(),
+        alicePositionMint[0].publicKey.toString()
+      );
+      assert.equal(await getTokenBalance(alicePositionTokenAccount[0]), 1);
+
+      const walletPurchaseAccount =
+        await program.account.walletPurchaseAccount.fetch(
+          aliceWalletPurchasePubkey[0]
+        );
+      assert.equal(walletPurchaseAccount.purchased.toNumber(), 5);
 
       assert.equal(
         await getTokenBalance(aliceAuryTokenAccount),
@@ -370,19 +864,23 @@ describe('mine-together', () => {
       assert.equal(await getTokenBalance(auryVaultPubkey), 50_000_000_000);
     });
 
-    it('Purchase limited miner - failed', async () => {
+    it('Purchase miner above max total cost - failed', async () => {
+      await newPosition(1);
       await assert.rejects(
         async () => {
           await program.rpc.purchaseMiner(
             configBump,
             aliceUserMinerBump[1],
             auryVaultBump,
-            minerLimit[1].add(new anchor.BN(1)),
+            aliceWalletPurchaseBump[1],
+            minerLimit[1],
+            minerCost[1].mul(minerLimit[1]).sub(new anchor.BN(1)),
             {
               accounts: {
                 configAccount: configPubkey,
                 minerAccount: minerPubkey[1],
                 userMinerAccount: aliceUserMinerPubkey[1],
+                walletPurchaseAccount: aliceWalletPurchasePubkey[1],
                 auryMint: auryMintPubkey,
                 auryVault: auryVaultPubkey,
                 auryFrom: aliceAuryTokenAccount,
@@ -390,36 +888,81 @@ describe('mine-together', () => {
                 systemProgram: anchor.web3.SystemProgram.programId,
                 tokenProgram: TOKEN_PROGRAM_ID,
                 rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                ...positionAccounts(1),
               },
+              signers: [alicePositionMint[1]],
             }
           );
         },
         {
-          code: 6002,
-          message: '6002: Miner purchase limit',
+          code: 6027,
+          message: '6027: Miner price above max',
         }
       );
     });
 
-    it('Purchase limited miner - success', async () => {
-      await program.rpc.purchaseMiner(
-        configBump,
-        aliceUserMinerBump[1],
-        auryVaultBump,
-        minerLimit[1],
-        {
-          accounts: {
-            configAccount: configPubkey,
-            minerAccount: minerPubkey[1],
-            userMinerAccount: aliceUserMinerPubkey[1],
-            auryMint: auryMintPubkey,
-            auryVault: auryVaultPubkey,
-            auryFrom: aliceAuryTokenAccount,
-            auryFromAuthority: alicePubkey,
-            systemProgram: anchor.web3.SystemProgram.programId,
+    it('Purchase limited miner - failed', async () => {
+      await newPosition(1);
+      await assert.rejects(
+        async () => {
+          await program.rpc.purchaseMiner(
+            configBump,
+            aliceUserMinerBump[1],
+            auryVaultBump,
+            aliceWalletPurchaseBump[1],
+            minerLimit[1].add(new anchor.BN(1)),
+            maxTotalCost,
+            {
+              accounts: {
+                configAccount: configPubkey,
+                minerAccount: minerPubkey[1],
+                userMinerAccount: aliceUserMinerPubkey[1],
+                walletPurchaseAccount: aliceWalletPurchasePubkey[1],
+                auryMint: auryMintPubkey,
+                auryVault: auryVaultPubkey,
+                auryFrom: aliceAuryTokenAccount,
+                auryFromAuthority: alicePubkey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                ...positionAccounts(1),
+              },
+              signers: [alicePositionMint[1]],
+            }
+          );
+        },
+        {
+          code: 6002,
+          message: '6002: Miner purchase limit',
+        }
+      );
+    });
+
+    it('Purchase limited miner - success', async () => {
+      await newPosition(1);
+      await program.rpc.purchaseMiner(
+        configBump,
+        aliceUserMinerBump[1],
+        auryVaultBump,
+        aliceWalletPurchaseBump[1],
+        minerLimit[1],
+        maxTotalCost,
+        {
+          accounts: {
+            configAccount: configPubkey,
+            minerAccount: minerPubkey[1],
+            userMinerAccount: aliceUserMinerPubkey[1],
+            walletPurchaseAccount: aliceWalletPurchasePubkey[1],
+            auryMint: auryMintPubkey,
+            auryVault: auryVaultPubkey,
+            auryFrom: aliceAuryTokenAccount,
+            auryFromAuthority: alicePubkey,
+            systemProgram: anchor.web3.SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
             rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            ...positionAccounts(1),
           },
+          signers: [alicePositionMint[1]],
         }
       );
 
@@ -463,26 +1006,42 @@ describe('mine-together', () => {
         ],
         program.programId
       );
+      [mineFeesPubkey, mineFeesBump] =
+        await anchor.web3.PublicKey.findProgramAddress(
+          [
+            minePubkey.toBuffer(),
+            Buffer.from(
+              anchor.utils.bytes.utf8.encode('MINE_TOGETHER_MINE_FEES')
+            ),
+          ],
+          program.programId
+        );
 
-      await program.rpc.createMine(configBump, mineBump, mineName, mineFee, {
-        accounts: {
-          configAccount: configPubkey,
-          mineAccount: minePubkey,
-          feeTo: aliceAuryTokenAccount,
-          owner: alicePubkey,
-          systemProgram: anchor.web3.SystemProgram.programId,
-          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
-        },
-      });
+      await program.rpc.createMine(
+        configBump,
+        mineBump,
+        mineFeesBump,
+        mineName,
+        mineFee,
+        mineUri,
+        {
+          accounts: {
+            configAccount: configPubkey,
+            mineAccount: minePubkey,
+            mineFeesAccount: mineFeesPubkey,
+            owner: alicePubkey,
+            systemProgram: anchor.web3.SystemProgram.programId,
+            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+          },
+        }
+      );
 
       const mineAccount = await program.account.mineAccount.fetch(minePubkey);
+      assert.equal(mineAccount.version, 1);
       assert.equal(mineAccount.owner.toString(), alicePubkey.toString());
       assert.equal(mineAccount.name, mineName);
+      assert.equal(mineAccount.uri, mineUri);
       assert.equal(mineAccount.fee.toNumber(), mineFee.toNumber());
-      assert.equal(
-        mineAccount.feeTo.toString(),
-        aliceAuryTokenAccount.toString()
-      );
       assert.equal(mineAccount.totalAmount.toNumber(), 0);
       assert.equal(mineAccount.xTotalAmount.toNumber(), 0);
       assert.equal(mineAccount.lastUpdatedAt.toNumber(), 0);
@@ -491,36 +1050,313 @@ describe('mine-together', () => {
 
     it('Create mine again - failed', async () => {
       await assert.rejects(async () => {
-        await program.rpc.createMine(configBump, mineBump, mineName, mineFee, {
+        await program.rpc.createMine(
+          configBump,
+          mineBump,
+          mineFeesBump,
+          mineName,
+          mineFee,
+          mineUri,
+          {
+            accounts: {
+              configAccount: configPubkey,
+              mineAccount: minePubkey,
+              mineFeesAccount: mineFeesPubkey,
+              owner: alicePubkey,
+              systemProgram: anchor.web3.SystemProgram.programId,
+              rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            },
+          }
+        );
+      });
+    });
+
+    it('Update mine with invalid name - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.updateMine(
+            configBump,
+            mineBump,
+            'M'.repeat(51),
+            mineFee,
+            mineUri,
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                owner: alicePubkey,
+              },
+            }
+          );
+        },
+        {
+          code: 6011,
+          message: '6011: Name too long',
+        }
+      );
+
+      await assert.rejects(
+        async () => {
+          await program.rpc.updateMine(
+            configBump,
+            mineBump,
+            '\u0410urory', // cyrillic A
+            mineFee,
+            mineUri,
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                owner: alicePubkey,
+              },
+            }
+          );
+        },
+        {
+          code: 6012,
+          message: '6012: Invalid name',
+        }
+      );
+    });
+
+    it('Update mine', async () => {
+      await program.rpc.updateMine(
+        configBump,
+        mineBump,
+        mineName,
+        mineFee,
+        null,
+        {
           accounts: {
             configAccount: configPubkey,
             mineAccount: minePubkey,
-            feeTo: aliceAuryTokenAccount,
             owner: alicePubkey,
-            systemProgram: anchor.web3.SystemProgram.programId,
-            rent: anchor.web3.SYSVAR_RENT_PUBKEY,
           },
-        });
-      });
+        }
+      );
+
+      const mineAccount = await program.account.mineAccount.fetch(minePubkey);
+      assert.equal(mineAccount.owner.toString(), alicePubkey.toString());
+      assert.equal(mineAccount.name, mineName);
+      assert.equal(mineAccount.uri, mineUri);
+      assert.equal(mineAccount.fee.toNumber(), mineFee.toNumber());
     });
 
-    it('Update mine', async () => {
-      await program.rpc.updateMine(configBump, mineBump, mineName, mineFee, {
+    it('Update mine capacity', async () => {
+      await program.rpc.updateMineCapacity(
+        mineBump,
+        new anchor.BN(1_000_000_000_000),
+        new anchor.BN(10),
+        new anchor.BN(10_000_000_000),
+        {
+          accounts: {
+            mineAccount: minePubkey,
+            owner: alicePubkey,
+          },
+        }
+      );
+
+      const mineAccount = await program.account.mineAccount.fetch(minePubkey);
+      assert.equal(mineAccount.maxTotalAmount.toNumber(), 1_000_000_000_000);
+      assert.equal(mineAccount.maxMiners.toNumber(), 10);
+      assert.equal(mineAccount.minPower.toNumber(), 10_000_000_000);
+      assert.equal(mineAccount.activeMiners.toNumber(), 0);
+    });
+
+    it('Update mine access without a collection - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.updateMineAccess(
+            mineBump,
+            { collection: {} },
+            PublicKey.default,
+            {
+              accounts: {
+                mineAccount: minePubkey,
+                owner: alicePubkey,
+              },
+            }
+          );
+        },
+        {
+          code: 6041,
+          message: '6041: Invalid mine access',
+        }
+      );
+    });
+
+    it('Add / remove mine member', async () => {
+      const [mineMemberPubkey, mineMemberBump] =
+        await anchor.web3.PublicKey.findProgramAddress(
+          [
+            minePubkey.toBuffer(),
+            Buffer.from(
+              anchor.utils.bytes.utf8.encode('MINE_TOGETHER_MINE_MEMBER')
+            ),
+            bobPubkey.toBuffer(),
+          ],
+          program.programId
+        );
+
+      await program.rpc.addMineMember(mineBump, mineMemberBump, bobPubkey, {
+        accounts: {
+          mineAccount: minePubkey,
+          mineMemberAccount: mineMemberPubkey,
+          owner: alicePubkey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        },
+      });
+
+      const mineMemberAccount = await program.account.mineMemberAccount.fetch(
+        mineMemberPubkey
+      );
+      assert.equal(mineMemberAccount.mine.toString(), minePubkey.toString());
+      assert.equal(mineMemberAccount.member.toString(), bobPubkey.toString());
+
+      await program.rpc.removeMineMember(mineBump, {
         accounts: {
-          configAccount: configPubkey,
           mineAccount: minePubkey,
-          feeTo: bobAuryTokenAccount,
+          mineMemberAccount: mineMemberPubkey,
           owner: alicePubkey,
         },
       });
 
+      await assert.rejects(
+        async () => {
+          await program.account.mineMemberAccount.fetch(mineMemberPubkey);
+        },
+        {
+          message: 'Account does not exist ' + mineMemberPubkey.toString(),
+        }
+      );
+
       const mineAccount = await program.account.mineAccount.fetch(minePubkey);
-      assert.equal(mineAccount.owner.toString(), alicePubkey.toString());
+      assert.deepEqual(mineAccount.access, { public: {} });
+    });
+
+    it('Migrate mine (already current)', async () => {
+      // anyone may migrate a mine
+      await program.rpc.migrateMine(mineFeesBump, {
+        accounts: {
+          mineAccount: minePubkey,
+          mineFeesAccount: mineFeesPubkey,
+          payer: bobPubkey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        },
+        signers: [bob],
+      });
+
+      const mineAccount = await program.account.mineAccount.fetch(minePubkey);
+      assert.equal(mineAccount.version, 1);
       assert.equal(mineAccount.name, mineName);
+    });
+
+    it('Purchase and mine above max total cost - failed', async () => {
+      await newPosition(2);
+      await assert.rejects(
+        async () => {
+          await program.rpc.purchaseAndMine(
+            configBump,
+            aliceUserMinerBump[2],
+            auryVaultBump,
+            aliceWalletPurchaseBump[2],
+            new anchor.BN(1),
+            new anchor.BN(0),
+            new anchor.BN(0),
+            {
+              accounts: {
+                configAccount: configPubkey,
+                minerAccount: minerPubkey[2],
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                userMinerAccount: aliceUserMinerPubkey[2],
+                walletPurchaseAccount: aliceWalletPurchasePubkey[2],
+                auryMint: auryMintPubkey,
+                auryVault: auryVaultPubkey,
+                auryFrom: aliceAuryTokenAccount,
+                auryFromAuthority: alicePubkey,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+                ...positionAccounts(2),
+              },
+              signers: [alicePositionMint[2]],
+            }
+          );
+        },
+        {
+          code: 6027,
+          message: '6027: Miner price above max',
+        }
+      );
+    });
+
+    it('Sync mine fee (already within bounds)', async () => {
+      await program.rpc.syncMineFee(configBump, {
+        accounts: {
+          configAccount: configPubkey,
+          mineAccount: minePubkey,
+          mineFeesAccount: mineFeesPubkey,
+        },
+      });
+
+      const mineAccount = await program.account.mineAccount.fetch(minePubkey);
       assert.equal(mineAccount.fee.toNumber(), mineFee.toNumber());
-      assert.equal(
-        mineAccount.feeTo.toString(),
-        bobAuryTokenAccount.toString()
+    });
+
+    it('Update mine fee splits with invalid shares - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.updateMineFeeSplits(
+            configBump,
+            mineBump,
+            [
+              { recipient: aliceAuryTokenAccount, share: new anchor.BN(5_000) },
+              { recipient: bobAuryTokenAccount, share: new anchor.BN(4_000) },
+            ],
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                owner: alicePubkey,
+              },
+            }
+          );
+        },
+        {
+          code: 6037,
+          message: '6037: Invalid fee splits',
+        }
+      );
+    });
+
+    it('Update mine fee splits to a wallet - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.updateMineFeeSplits(
+            configBump,
+            mineBump,
+            [{ recipient: alicePubkey, share: new anchor.BN(10_000) }],
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                owner: alicePubkey,
+              },
+              remainingAccounts: [
+                { pubkey: alicePubkey, isWritable: false, isSigner: false },
+              ],
+            }
+          );
+        },
+        {
+          code: 6037,
+          message: '6037: Invalid fee splits',
+        }
       );
     });
 
@@ -541,25 +1377,35 @@ describe('mine-together', () => {
     it('add alice miner-A to mine', async () => {
       const lowerDate = Math.floor(Date.now() / 1000 - 1);
 
-      await program.rpc.addMinersToMine(aliceUserMinerBump[0], {
-        accounts: {
-          mineAccount: minePubkey,
-          userMinerAccount: aliceUserMinerPubkey[0],
-          owner: alicePubkey,
-        },
-      });
+      await program.rpc.addMinersToMine(
+        aliceUserMinerBump[0],
+        new anchor.BN(50_000_000_000),
+        {
+          accounts: {
+            mineAccount: minePubkey,
+            mineFeesAccount: mineFeesPubkey,
+            userMinerAccount: aliceUserMinerPubkey[0],
+            owner: alicePubkey,
+          },
+          remainingAccounts: positionProofs(0),
+        }
+      );
 
       const upperDate = Math.ceil(Date.now() / 1000 + 1);
 
       const mineAccount = await program.account.mineAccount.fetch(minePubkey);
       assert.equal(mineAccount.totalAmount.toNumber(), 50_000_000_000);
       assert.equal(mineAccount.xTotalAmount.toNumber(), 50_000_000_000);
+      assert.equal(mineAccount.activeMiners.toNumber(), 1);
 
       const userMinerAccount = await program.account.userMinerAccount.fetch(
         aliceUserMinerPubkey[0]
       );
       assert.equal(userMinerAccount.xAuryAmount.toNumber(), 50_000_000_000);
+      assert.equal(userMinerAccount.boostedPower.toNumber(), 50_000_000_000);
       assert.equal(userMinerAccount.mineKey.toString(), minePubkey.toString());
+      assert.equal(userMinerAccount.lockedFee.toNumber(), mineFee.toNumber());
+      assert.equal(userMinerAccount.feeLocked, true);
       expect(userMinerAccount.miningStartAt.toNumber()).to.be.at.least(
         lowerDate
       );
@@ -568,6 +1414,30 @@ describe('mine-together', () => {
       );
     });
 
+    it('add alice miner-A to mine again - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.addMinersToMine(
+            aliceUserMinerBump[0],
+            new anchor.BN(0),
+            {
+              accounts: {
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                userMinerAccount: aliceUserMinerPubkey[0],
+                owner: alicePubkey,
+              },
+              remainingAccounts: positionProofs(0),
+            }
+          );
+        },
+        {
+          code: 6031,
+          message: '6031: Miner already mining',
+        }
+      );
+    });
+
     it('Reward to mine', async () => {
       const lowerDate = Math.floor(Date.now() / 1000 - 1);
 
@@ -612,27 +1482,58 @@ describe('mine-together', () => {
       );
     });
 
+    it('add alice miner-B to mine below min x shares - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.addMinersToMine(
+            aliceUserMinerBump[1],
+            new anchor.BN(36_428_571_429),
+            {
+              accounts: {
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                userMinerAccount: aliceUserMinerPubkey[1],
+                owner: alicePubkey,
+              },
+              remainingAccounts: positionProofs(1),
+            }
+          );
+        },
+        {
+          code: 6035,
+          message: '6035: X shares below min',
+        }
+      );
+    });
+
     it('add alice miner-B to mine', async () => {
       const lowerDate = Math.floor(Date.now() / 1000 - 1);
 
-      await program.rpc.addMinersToMine(aliceUserMinerBump[1], {
-        accounts: {
-          mineAccount: minePubkey,
-          userMinerAccount: aliceUserMinerPubkey[1],
-          owner: alicePubkey,
-        },
-      });
+      await program.rpc.addMinersToMine(
+        aliceUserMinerBump[1],
+        new anchor.BN(36_428_571_428),
+        {
+          accounts: {
+            mineAccount: minePubkey,
+            mineFeesAccount: mineFeesPubkey,
+            userMinerAccount: aliceUserMinerPubkey[1],
+            owner: alicePubkey,
+          },
+          remainingAccounts: positionProofs(1),
+        }
+      );
 
       const upperDate = Math.ceil(Date.now() / 1000 + 1);
 
       const mineAccount = await program.account.mineAccount.fetch(minePubkey);
       assert.equal(mineAccount.totalAmount.toNumber(), 95_000_000_000);
-      assert.equal(mineAccount.xTotalAmount.toNumber(), 86_363_636_363); // 36_363_636_363 = 40_000_000_000 * 5 / 5.5
+      assert.equal(mineAccount.xTotalAmount.toNumber(), 86_428_571_428); // 36_428_571_428 = 40_000_000_000 * 51 / 56
+      assert.equal(mineAccount.activeMiners.toNumber(), 2);
 
       const userMinerAccount = await program.account.userMinerAccount.fetch(
         aliceUserMinerPubkey[1]
       );
-      assert.equal(userMinerAccount.xAuryAmount.toNumber(), 36_363_636_363);
+      assert.equal(userMinerAccount.xAuryAmount.toNumber(), 36_428_571_428);
       assert.equal(userMinerAccount.mineKey.toString(), minePubkey.toString());
       expect(userMinerAccount.miningStartAt.toNumber()).to.be.at.least(
         lowerDate
@@ -668,7 +1569,7 @@ describe('mine-together', () => {
 
       const mineAccount = await program.account.mineAccount.fetch(minePubkey);
       assert.equal(mineAccount.totalAmount.toNumber(), 100_000_000_000);
-      assert.equal(mineAccount.xTotalAmount.toNumber(), 86_363_636_363);
+      assert.equal(mineAccount.xTotalAmount.toNumber(), 86_428_571_428);
 
       const shares = mineAccount.shares as {
         timestamp;
@@ -681,7 +1582,7 @@ describe('mine-together', () => {
       assert.equal(shares[0].tokenAmount.toNumber(), 55_000_000_000);
       assert.equal(shares[0].xTokenAmount.toNumber(), 50_000_000_000);
       assert.equal(shares[1].tokenAmount.toNumber(), 100_000_000_000);
-      assert.equal(shares[1].xTokenAmount.toNumber(), 86_363_636_363);
+      assert.equal(shares[1].xTokenAmount.toNumber(), 86_428_571_428);
 
       assert.equal(await getTokenBalance(auryVaultPubkey), 100_000_000_000);
       assert.equal(
@@ -693,16 +1594,20 @@ describe('mine-together', () => {
 
   describe('Claim miner', async () => {
     it('Claim alice miner-C - failed (not added)', async () => {
+      await newPosition(2);
       await program.rpc.purchaseMiner(
         configBump,
         aliceUserMinerBump[2],
         auryVaultBump,
+        aliceWalletPurchaseBump[2],
         new anchor.BN(10),
+        maxTotalCost,
         {
           accounts: {
             configAccount: configPubkey,
             minerAccount: minerPubkey[2],
             userMinerAccount: aliceUserMinerPubkey[2],
+            walletPurchaseAccount: aliceWalletPurchasePubkey[2],
             auryMint: auryMintPubkey,
             auryVault: auryVaultPubkey,
             auryFrom: aliceAuryTokenAccount,
@@ -710,7 +1615,9 @@ describe('mine-together', () => {
             systemProgram: anchor.web3.SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
             rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            ...positionAccounts(2),
           },
+          signers: [alicePositionMint[2]],
         }
       );
 
@@ -722,73 +1629,247 @@ describe('mine-together', () => {
 
       await assert.rejects(
         async () => {
-          await program.rpc.claimMiner(aliceUserMinerBump[2], auryVaultBump, {
-            accounts: {
-              mineAccount: minePubkey,
-              userMinerAccount: aliceUserMinerPubkey[2],
-              auryMint: auryMintPubkey,
-              auryVault: auryVaultPubkey,
-              auryTo: aliceAuryTokenAccount,
-              feeTo: bobAuryTokenAccount,
-              auryToAuthority: alicePubkey,
-              tokenProgram: TOKEN_PROGRAM_ID,
-            },
-          });
+          await program.rpc.claimMiner(
+            configBump,
+            aliceUserMinerBump[2],
+            auryVaultBump,
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                userMinerAccount: aliceUserMinerPubkey[2],
+                auryMint: auryMintPubkey,
+                auryVault: auryVaultPubkey,
+                auryTo: aliceAuryTokenAccount,
+                treasury: bobAuryTokenAccount,
+                auryToAuthority: alicePubkey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+              },
+              remainingAccounts: positionProofs(2, true),
+            }
+          );
+        },
+        {
+          code: 6008,
+          message: '6008: Claim unavailable',
+        }
+      );
+    });
+
+    it('Auto claim alice miner-C - failed (not added)', async () => {
+      await program.rpc.updateAutoClaim(
+        configBump,
+        aliceUserMinerBump[2],
+        true,
+        {
+          accounts: {
+            configAccount: configPubkey,
+            userMinerAccount: aliceUserMinerPubkey[2],
+            owner: alicePubkey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          remainingAccounts: positionProofs(2, true),
+        }
+      );
+
+      const userMinerAccount = await program.account.userMinerAccount.fetch(
+        aliceUserMinerPubkey[2]
+      );
+      assert.equal(userMinerAccount.autoClaim, true);
+
+      // the position nft is delegated to the config account for the keeper
+      // to burn
+      const positionTokenAccount =
+        await provider.connection.getParsedAccountInfo(
+          alicePositionTokenAccount[2]
+        );
+      assert.equal(
+        (positionTokenAccount.value.data as any).parsed.info.delegate,
+        configPubkey.toString()
+      );
+
+      await assert.rejects(
+        async () => {
+          await program.rpc.autoClaimMiner(
+            configBump,
+            aliceUserMinerBump[2],
+            auryVaultBump,
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                userMinerAccount: aliceUserMinerPubkey[2],
+                auryMint: auryMintPubkey,
+                auryVault: auryVaultPubkey,
+                auryTo: aliceAuryTokenAccount,
+                treasury: bobAuryTokenAccount,
+                keeperTo: bobAuryTokenAccount,
+                owner: alicePubkey,
+                keeper: bobPubkey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+              },
+              signers: [bob],
+              remainingAccounts: positionProofs(2, true),
+            }
+          );
         },
         {
-          code: 6009,
-          message: '6009: Claim unavailable',
+          code: 6008,
+          message: '6008: Claim unavailable',
         }
       );
     });
 
-    it('Claim alice miner-A - failed (wrong feeTo)', async () => {
+    it('Claim many alice miner-C - failed (not added)', async () => {
       await assert.rejects(
         async () => {
-          await program.rpc.claimMiner(aliceUserMinerBump[0], auryVaultBump, {
+          await program.rpc.claimMany(configBump, auryVaultBump, {
             accounts: {
+              configAccount: configPubkey,
               mineAccount: minePubkey,
-              userMinerAccount: aliceUserMinerPubkey[0],
+              mineFeesAccount: mineFeesPubkey,
               auryMint: auryMintPubkey,
               auryVault: auryVaultPubkey,
               auryTo: aliceAuryTokenAccount,
-              feeTo: aliceAuryTokenAccount,
+              treasury: bobAuryTokenAccount,
               auryToAuthority: alicePubkey,
               tokenProgram: TOKEN_PROGRAM_ID,
             },
+            remainingAccounts: [
+              {
+                pubkey: aliceUserMinerPubkey[2],
+                isWritable: true,
+                isSigner: false,
+              },
+              ...positionProofs(2, true),
+            ],
           });
         },
         {
-          code: 6010,
-          message: '6010: Invalid fee account',
+          code: 6008,
+          message: '6008: Claim unavailable',
         }
       );
     });
 
-    it('Claim alice miner-A - success', async () => {
-      await program.rpc.claimMiner(aliceUserMinerBump[0], auryVaultBump, {
-        accounts: {
-          mineAccount: minePubkey,
-          userMinerAccount: aliceUserMinerPubkey[0],
-          auryMint: auryMintPubkey,
-          auryVault: auryVaultPubkey,
-          auryTo: aliceAuryTokenAccount,
-          feeTo: bobAuryTokenAccount,
-          auryToAuthority: alicePubkey,
-          tokenProgram: TOKEN_PROGRAM_ID,
+    it('Harvest alice miner-C - failed (not added)', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.harvest(
+            configBump,
+            aliceUserMinerBump[2],
+            auryVaultBump,
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                userMinerAccount: aliceUserMinerPubkey[2],
+                auryMint: auryMintPubkey,
+                auryVault: auryVaultPubkey,
+                auryTo: aliceAuryTokenAccount,
+                treasury: bobAuryTokenAccount,
+                auryToAuthority: alicePubkey,
+                tokenProgram: TOKEN_PROGRAM_ID,
+              },
+              remainingAccounts: positionProofs(2),
+            }
+          );
         },
-      });
+        {
+          code: 6008,
+          message: '6008: Claim unavailable',
+        }
+      );
+    });
+
+    it('Restake alice miner-C - failed (not added)', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.restakeMiner(
+            configBump,
+            aliceUserMinerBump[2],
+            aliceUserMinerBump[2],
+            auryVaultBump,
+            aliceWalletPurchaseBump[2],
+            new anchor.BN(1),
+            new anchor.BN(0),
+            {
+              accounts: {
+                configAccount: configPubkey,
+                mineAccount: minePubkey,
+                mineFeesAccount: mineFeesPubkey,
+                userMinerAccount: aliceUserMinerPubkey[2],
+                newMinerAccount: minerPubkey[2],
+                walletPurchaseAccount: aliceWalletPurchasePubkey[2],
+                newMineAccount: minePubkey,
+                newMineFeesAccount: mineFeesPubkey,
+                newUserMinerAccount: aliceUserMinerPubkey[2],
+                auryMint: auryMintPubkey,
+                auryVault: auryVaultPubkey,
+                auryTo: aliceAuryTokenAccount,
+                treasury: bobAuryTokenAccount,
+                owner: alicePubkey,
+                tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+                systemProgram: anchor.web3.SystemProgram.programId,
+                tokenProgram: TOKEN_PROGRAM_ID,
+                rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+              },
+              remainingAccounts: [
+                ...positionProofs(2),
+                {
+                  pubkey: alicePositionMetadata[2],
+                  isWritable: true,
+                  isSigner: false,
+                },
+              ],
+            }
+          );
+        },
+        {
+          code: 6008,
+          message: '6008: Claim unavailable',
+        }
+      );
+    });
+
+    it('Claim alice miner-A - success', async () => {
+      await program.rpc.claimMiner(
+        configBump,
+        aliceUserMinerBump[0],
+        auryVaultBump,
+        {
+          accounts: {
+            configAccount: configPubkey,
+            mineAccount: minePubkey,
+            mineFeesAccount: mineFeesPubkey,
+            userMinerAccount: aliceUserMinerPubkey[0],
+            auryMint: auryMintPubkey,
+            auryVault: auryVaultPubkey,
+            auryTo: aliceAuryTokenAccount,
+            treasury: bobAuryTokenAccount,
+            auryToAuthority: alicePubkey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          remainingAccounts: positionProofs(0, true),
+        }
+      );
 
-      // TotalReward: 5_000_000_000
-      // Fee: 5_000_000_000 * 20% = 1_000_000_000
-      // UserReward: 4_000_000_000
+      // TotalReward: 50_000_000_000 * 56 / 51 - 50_000_000_000 = 4_901_960_784
+      // Fee: 4_901_960_784 * 20% = 980_392_157 (accrued in the vault)
+      // UserReward: 3_921_568_627
       // UserPower: 50_000_000_000
-      assert.equal(await getTokenBalance(auryVaultPubkey), 345_000_000_000);
+      assert.equal(await getTokenBalance(auryVaultPubkey), 346_078_431_373);
       assert.equal(
         await getTokenBalance(aliceAuryTokenAccount),
-        654_000_000_000
+        653_921_568_627
       );
-      assert.equal(await getTokenBalance(bobAuryTokenAccount), 1_000_000_000);
+      assert.equal(await getTokenBalance(bobAuryTokenAccount), 0);
+
+      const mineAccount = await program.account.mineAccount.fetch(minePubkey);
+      assert.equal(mineAccount.accruedFees.toNumber(), 980_392_157);
 
       await assert.rejects(
         async () => {
@@ -802,29 +1883,44 @@ describe('mine-together', () => {
     });
 
     it('Claim alice miner-B - success', async () => {
-      await program.rpc.claimMiner(aliceUserMinerBump[1], auryVaultBump, {
-        accounts: {
-          mineAccount: minePubkey,
-          userMinerAccount: aliceUserMinerPubkey[1],
-          auryMint: auryMintPubkey,
-          auryVault: auryVaultPubkey,
-          auryTo: aliceAuryTokenAccount,
-          feeTo: bobAuryTokenAccount,
-          auryToAuthority: alicePubkey,
-          tokenProgram: TOKEN_PROGRAM_ID,
-        },
-      });
+      await program.rpc.claimMiner(
+        configBump,
+        aliceUserMinerBump[1],
+        auryVaultBump,
+        {
+          accounts: {
+            configAccount: configPubkey,
+            mineAccount: minePubkey,
+            mineFeesAccount: mineFeesPubkey,
+            userMinerAccount: aliceUserMinerPubkey[1],
+            auryMint: auryMintPubkey,
+            auryVault: auryVaultPubkey,
+            auryTo: aliceAuryTokenAccount,
+            treasury: bobAuryTokenAccount,
+            auryToAuthority: alicePubkey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          remainingAccounts: positionProofs(1, true),
+        }
+      );
 
-      // TotalReward: 2_105_263_157
-      // Fee: 2_105_263_157 * 20% = 421_052_632
-      // UserReward: 1_684_210_525
+      // TotalReward: 2_083_333_332
+      // Fee: 2_083_333_332 * 20% = 416_666_667 (accrued in the vault)
+      // UserReward: 1_666_666_665
       // UserPower: 40_000_000_000
-      assert.equal(await getTokenBalance(auryVaultPubkey), 302_894_736_843);
+      assert.equal(await getTokenBalance(auryVaultPubkey), 304_411_764_708);
       assert.equal(
         await getTokenBalance(aliceAuryTokenAccount),
-        695_684_210_525
+        695_588_235_292
       );
-      assert.equal(await getTokenBalance(bobAuryTokenAccount), 1_421_052_632);
+      assert.equal(await getTokenBalance(bobAuryTokenAccount), 0);
+
+      // what the last miner leaves behind waits for the next rewards
+      const mineAccount = await program.account.mineAccount.fetch(minePubkey);
+      assert.equal(mineAccount.totalAmount.toNumber(), 0);
+      assert.equal(mineAccount.xTotalAmount.toNumber(), 0);
+      assert.equal(mineAccount.pendingRewards.toNumber(), 3_014_705_884);
+      assert.equal(mineAccount.accruedFees.toNumber(), 1_397_058_824);
 
       await assert.rejects(
         async () => {
@@ -838,18 +1934,87 @@ describe('mine-together', () => {
     });
   });
 
+  describe('Mine fees', async () => {
+    it('Distribute mine fees without splits - failed', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.distributeMineFees(auryVaultBump, {
+            accounts: {
+              mineAccount: minePubkey,
+              mineFeesAccount: mineFeesPubkey,
+              auryMint: auryMintPubkey,
+              auryVault: auryVaultPubkey,
+              tokenProgram: TOKEN_PROGRAM_ID,
+            },
+          });
+        },
+        {
+          code: 6037,
+          message: '6037: Invalid fee splits',
+        }
+      );
+    });
+
+    it('Withdraw mine fees - failed (not owner)', async () => {
+      await assert.rejects(
+        async () => {
+          await program.rpc.withdrawMineFees(auryVaultBump, {
+            accounts: {
+              mineAccount: minePubkey,
+              mineFeesAccount: mineFeesPubkey,
+              auryMint: auryMintPubkey,
+              auryVault: auryVaultPubkey,
+              auryTo: aliceAuryTokenAccount,
+              owner: alicePubkey,
+              tokenProgram: TOKEN_PROGRAM_ID,
+            },
+          });
+        },
+        {
+          code: 6004,
+          message: '6004: Not mine owner',
+        }
+      );
+    });
+
+    it('Withdraw mine fees', async () => {
+      await program.rpc.withdrawMineFees(auryVaultBump, {
+        accounts: {
+          mineAccount: minePubkey,
+          mineFeesAccount: mineFeesPubkey,
+          auryMint: auryMintPubkey,
+          auryVault: auryVaultPubkey,
+          auryTo: bobAuryTokenAccount,
+          owner: bobPubkey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        },
+        signers: [bob],
+      });
+
+      assert.equal(await getTokenBalance(auryVaultPubkey), 303_014_705_884);
+      assert.equal(await getTokenBalance(bobAuryTokenAccount), 1_397_058_824);
+
+      const mineAccount = await program.account.mineAccount.fetch(minePubkey);
+      assert.equal(mineAccount.accruedFees.toNumber(), 0);
+    });
+  });
+
   describe('Repurchase miner', async () => {
     it('Purchase miner-A', async () => {
+      await newPosition(0);
       await program.rpc.purchaseMiner(
         configBump,
         aliceUserMinerBump[0],
         auryVaultBump,
+        aliceWalletPurchaseBump[0],
         new anchor.BN(5),
+        maxTotalCost,
         {
           accounts: {
             configAccount: configPubkey,
             minerAccount: minerPubkey[0],
             userMinerAccount: aliceUserMinerPubkey[0],
+            walletPurchaseAccount: aliceWalletPurchasePubkey[0],
             auryMint: auryMintPubkey,
             auryVault: auryVaultPubkey,
             auryFrom: aliceAuryTokenAccount,
@@ -857,7 +2022,9 @@ describe('mine-together', () => {
             systemProgram: anchor.web3.SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
             rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+            ...positionAccounts(0),
           },
+          signers: [alicePositionMint[0]],
         }
       );
 
@@ -881,6 +2048,74 @@ describe('mine-together', () => {
       );
       assert.equal(userMinerAccount.xAuryAmount.toNumber(), 0);
     });
+
+    it('Cancel miner-A', async () => {
+      const totalPurchased = (
+        await program.account.minerAccount.fetch(minerPubkey[0])
+      ).totalPurchased.toNumber();
+      const walletPurchased = (
+        await program.account.walletPurchaseAccount.fetch(
+          aliceWalletPurchasePubkey[0]
+        )
+      ).purchased.toNumber();
+      const vaultBalance = await getTokenBalance(auryVaultPubkey);
+      const aliceBalance = await getTokenBalance(aliceAuryTokenAccount);
+
+      await program.rpc.cancelMiner(
+        configBump,
+        aliceUserMinerBump[0],
+        auryVaultBump,
+        aliceWalletPurchaseBump[0],
+        {
+          accounts: {
+            configAccount: configPubkey,
+            minerAccount: minerPubkey[0],
+            userMinerAccount: aliceUserMinerPubkey[0],
+            walletPurchaseAccount: aliceWalletPurchasePubkey[0],
+            auryMint: auryMintPubkey,
+            auryVault: auryVaultPubkey,
+            auryTo: aliceAuryTokenAccount,
+            treasury: bobAuryTokenAccount,
+            auryToAuthority: alicePubkey,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          },
+          remainingAccounts: positionProofs(0, true),
+        }
+      );
+
+      assert.equal(
+        await getTokenBalance(auryVaultPubkey),
+        vaultBalance - 50_000_000_000
+      );
+      assert.equal(
+        await getTokenBalance(aliceAuryTokenAccount),
+        aliceBalance + 50_000_000_000
+      );
+
+      const minerAccount = await program.account.minerAccount.fetch(
+        minerPubkey[0]
+      );
+      assert.equal(minerAccount.totalPurchased.toNumber(), totalPurchased - 5);
+
+      const walletPurchaseAccount =
+        await program.account.walletPurchaseAccount.fetch(
+          aliceWalletPurchasePubkey[0]
+        );
+      assert.equal(
+        walletPurchaseAccount.purchased.toNumber(),
+        walletPurchased - 5
+      );
+
+      await assert.rejects(
+        async () => {
+          await program.account.userMinerAccount.fetch(aliceUserMinerPubkey[0]);
+        },
+        {
+          message:
+            'Account does not exist ' + aliceUserMinerPubkey[0].toString(),
+        }
+      );
+    });
   });
 });
 